@@ -1,4 +1,4 @@
-use crate::ray::Ray;
+use crate::ray::{gamma, Ray};
 use nalgebra::Point3;
 
 pub struct AABB {
@@ -11,8 +11,15 @@ impl AABB {
         AABB { min, max }
     }
 
-    // https://tavianator.com/2015/ray_box_nan.html
     pub fn intersects(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit_range(r, t_min, t_max).is_some()
+    }
+
+    // part of [t_min, t_max] the ray spends inside the box
+    // https://tavianator.com/2015/ray_box_nan.html
+    // The far distances grow by their rounding error so flat boxes, where
+    // both slabs meet at one distance, don't lose hits to rounding
+    pub fn hit_range(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
         let mut tmin = t_min;
         let mut tmax = t_max;
         for i in 0..3 {
@@ -20,12 +27,12 @@ impl AABB {
             let t1 = (self.min[i] - r.origin[i]) * inv_d;
             let t2 = (self.max[i] - r.origin[i]) * inv_d;
             tmin = tmin.max(t1.min(t2));
-            tmax = tmax.min(t1.max(t2));
+            tmax = tmax.min(t1.max(t2) * (1.0 + 2.0 * gamma(3)));
             if tmax < tmin.max(0.0) {
-                return false;
+                return None;
             }
         }
-        true
+        Some((tmin, tmax))
     }
 
     pub fn get_surrounding_aabb(box0: &AABB, box1: &AABB) -> AABB {
@@ -0,0 +1,518 @@
+use std::io;
+use std::sync::Arc;
+
+use ::gltf::camera::Projection;
+use ::gltf::khr_lights_punctual::Kind;
+use ::gltf::mesh::Mode;
+use ::gltf::{Document, Node, Primitive};
+use nalgebra::{Matrix3, Matrix4, Point2, Point3};
+
+use crate::asset::{white_matte, Asset};
+use crate::brdf::{GlossySpecular, Lambertian, PerfectSpecular};
+use crate::btdf::PerfectTransmitter;
+use crate::camera::{CameraSetting, SimpleCamera};
+use crate::color::Color;
+use crate::error::SceneError;
+use crate::geometric_object::{scale_direction, scale_point, TriangleMesh};
+use crate::light::{DirectionalLight, Intensity, PointLight, SpotLight};
+use crate::material::{Emissive, Material, Matte, Phong, Reflective, Transparent};
+use crate::model::Vec3;
+use crate::view_plane::ViewPlane;
+
+// https://www.khronos.org/registry/glTF/specs/2.0/glTF-2.0.html
+struct GltfImport<'a> {
+    file_name: &'a str,
+    buffers: Vec<::gltf::buffer::Data>,
+    scale: f64,
+    vp: &'a ViewPlane,
+    material_base: usize,
+    default_material_id: usize,
+}
+
+impl Asset {
+    // adds meshes, cameras and punctual lights of the default scene
+    pub fn add_gltf(
+        &mut self,
+        file_name: &str,
+        scale: f64,
+        vp: &ViewPlane,
+    ) -> Result<(), SceneError> {
+        let (document, buffers, _images) =
+            ::gltf::import(file_name).map_err(|e| gltf_error(file_name, e))?;
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| SceneError::Parse {
+                file: file_name.to_owned(),
+                line: None,
+                message: "no scene".to_owned(),
+            })?;
+
+        let material_base = self.next_material_id();
+        let import = GltfImport {
+            file_name,
+            buffers,
+            scale,
+            vp,
+            material_base,
+            // primitives without a material use one past the last glTF material
+            default_material_id: material_base + document.materials().len(),
+        };
+        for material in document.materials() {
+            let id = material_base + material.index().unwrap_or_default();
+            let material = import.translate_material(self, &material);
+            self.materials.insert(id, Box::new(material));
+        }
+        self.materials
+            .insert(import.default_material_id, Box::new(white_matte()));
+        self.warn_unsupported_gltf(file_name, &document);
+        for node in scene.nodes() {
+            import.add_node(self, &node, &Matrix4::identity())?;
+        }
+        Ok(())
+    }
+
+    fn warn_unsupported_gltf(&mut self, file_name: &str, document: &Document) {
+        let ignored = [
+            ("animations", document.animations().count()),
+            ("skins", document.skins().count()),
+            ("textures", document.textures().count()),
+        ];
+        for (name, count) in ignored {
+            if count > 0 {
+                self.warnings
+                    .push(format!("{}: {} {} ignored", file_name, count, name));
+            }
+        }
+    }
+}
+
+impl<'a> GltfImport<'a> {
+    fn add_node(
+        &self,
+        asset: &mut Asset,
+        node: &Node,
+        parent: &Matrix4<f64>,
+    ) -> Result<(), SceneError> {
+        let local = node.transform().matrix();
+        let transform = parent * Matrix4::from_fn(|r, c| local[c][r] as f64);
+        let name = node.name().unwrap_or("unnamed");
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(asset, name, &primitive, &transform)?;
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.add_camera(asset, name, &camera, &transform);
+        }
+        if let Some(light) = node.light() {
+            self.add_light(asset, name, &light, &transform);
+        }
+        for child in node.children() {
+            self.add_node(asset, &child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn add_primitive(
+        &self,
+        asset: &mut Asset,
+        name: &str,
+        primitive: &Primitive,
+        transform: &Matrix4<f64>,
+    ) -> Result<(), SceneError> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<Point3<f64>> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|p| transform.transform_point(&to_point(p)))
+                .collect(),
+            None => {
+                asset.warnings.push(format!(
+                    "{}: mesh `{}` primitive without positions ignored",
+                    self.file_name, name
+                ));
+                return Ok(());
+            }
+        };
+        // normals transform with the inverse transpose
+        let normal_matrix = transform
+            .fixed_slice::<3, 3>(0, 0)
+            .try_inverse()
+            .unwrap_or_else(Matrix3::identity)
+            .transpose();
+        let normals: Option<Vec<Vec3>> = reader.read_normals().map(|normals| {
+            normals
+                .map(|n| normal_matrix * Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64))
+                .collect()
+        });
+        let uvs: Option<Vec<Point2<f64>>> = reader.read_tex_coords(0).map(|uvs| {
+            uvs.into_f32()
+                .map(|uv| Point2::new(uv[0] as f64, uv[1] as f64))
+                .collect()
+        });
+        let colors: Option<Vec<Color>> = reader.read_colors(0).map(|colors| {
+            colors
+                .into_rgb_f32()
+                .map(|c| Color::new(c[0] as f64, c[1] as f64, c[2] as f64))
+                .collect()
+        });
+        let indices: Vec<u32> = reader.read_indices().map_or_else(
+            || (0..positions.len() as u32).collect(),
+            |indices| indices.into_u32().collect(),
+        );
+        if let Some(i) = indices.iter().find(|i| **i as usize >= positions.len()) {
+            return Err(SceneError::Parse {
+                file: self.file_name.to_owned(),
+                line: None,
+                message: format!(
+                    "mesh `{}` references vertex {} of {}",
+                    name,
+                    i,
+                    positions.len()
+                ),
+            });
+        }
+
+        let faces: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|f| [f[0], f[1], f[2]])
+                .collect(),
+            Mode::TriangleStrip => (2..indices.len())
+                .map(|i| {
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            mode => {
+                asset.warnings.push(format!(
+                    "{}: mesh `{}` primitive mode {:?} ignored",
+                    self.file_name, name, mode
+                ));
+                return Ok(());
+            }
+        };
+
+        let material_id = primitive
+            .material()
+            .index()
+            .map_or(self.default_material_id, |i| self.material_base + i);
+
+        let mut mesh = TriangleMesh::new(material_id, positions, faces, self.scale);
+        if let Some(normals) = normals {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(uvs) = uvs {
+            mesh = mesh.with_uvs(uvs);
+        }
+        if let Some(colors) = colors {
+            mesh = mesh.with_colors(colors);
+        }
+        asset.add_mesh(mesh, self.file_name, &format!("mesh `{}`", name))
+    }
+
+    // Maps metallic-roughness onto the closest material, emission and
+    // transmission take precedence
+    fn translate_material(&self, asset: &mut Asset, material: &::gltf::Material) -> Material {
+        let name = material.name().unwrap_or("unnamed");
+        let pbr = material.pbr_metallic_roughness();
+        let base = pbr.base_color_factor();
+        let color = Color::new(base[0] as f64, base[1] as f64, base[2] as f64);
+        let metallic = pbr.metallic_factor() as f64;
+        let roughness = pbr.roughness_factor() as f64;
+
+        let textures = [
+            ("baseColorTexture", pbr.base_color_texture().is_some()),
+            (
+                "metallicRoughnessTexture",
+                pbr.metallic_roughness_texture().is_some(),
+            ),
+            ("normalTexture", material.normal_texture().is_some()),
+            ("occlusionTexture", material.occlusion_texture().is_some()),
+            ("emissiveTexture", material.emissive_texture().is_some()),
+        ];
+        for (texture, _) in textures.iter().filter(|(_, used)| *used) {
+            asset.warnings.push(format!(
+                "{}: material `{}` {} ignored, textures are not supported",
+                self.file_name, name, texture
+            ));
+        }
+        if !matches!(material.alpha_mode(), ::gltf::material::AlphaMode::Opaque) {
+            asset.warnings.push(format!(
+                "{}: material `{}` alpha mode ignored",
+                self.file_name, name
+            ));
+        }
+
+        let emissive = material.emissive_factor();
+        let emissive = Color::new(emissive[0] as f64, emissive[1] as f64, emissive[2] as f64);
+        if emissive.max() > 0.0 {
+            return Material::Emissive(Emissive::new(emissive.max(), emissive / emissive.max()));
+        }
+
+        let exp = roughness_to_exponent(roughness);
+        if let Some(transmission) = material.transmission() {
+            let kt = (transmission.transmission_factor() as f64).clamp(0.0, 0.9);
+            let ior = material.ior().unwrap_or(1.5) as f64;
+            return Material::Transparent(Transparent::new(
+                Lambertian::new(0.5, color),
+                Lambertian::new(0.9 - kt, color),
+                GlossySpecular::new(0.1, exp),
+                PerfectSpecular::new(0.1, Color::repeat(1.0)),
+                PerfectTransmitter::new(kt, ior),
+            ));
+        }
+
+        if metallic >= 0.5 {
+            Material::Reflective(Reflective::new(
+                Lambertian::new(0.5, color),
+                Lambertian::new(1.0 - metallic, color),
+                GlossySpecular::new(metallic * (1.0 - roughness), exp),
+                PerfectSpecular::new(metallic * (1.0 - roughness), color),
+            ))
+        } else if roughness < 1.0 {
+            let ks = 0.5 * (1.0 - roughness);
+            Material::Phong(Phong::new(
+                Lambertian::new(0.5, color),
+                Lambertian::new((1.0 - ks) * 0.99, color),
+                GlossySpecular::new(ks, exp),
+            ))
+        } else {
+            Material::Matte(Matte::new(
+                Lambertian::new(0.5, color),
+                Lambertian::new(1.0, color),
+            ))
+        }
+    }
+
+    // glTF cameras look down -z with +y up
+    fn add_camera(
+        &self,
+        asset: &mut Asset,
+        name: &str,
+        camera: &::gltf::Camera,
+        transform: &Matrix4<f64>,
+    ) {
+        let yfov = match camera.projection() {
+            Projection::Perspective(perspective) => perspective.yfov() as f64,
+            Projection::Orthographic(_) => {
+                asset.warnings.push(format!(
+                    "{}: orthographic camera `{}` ignored",
+                    self.file_name, name
+                ));
+                return;
+            }
+        };
+        let eye = transform.transform_point(&Point3::origin());
+        let lookat = transform.transform_point(&Point3::new(0.0, 0.0, -1.0));
+        let up = transform.transform_vector(&Vec3::y());
+        let view_plane_distance =
+            self.vp.vres as f64 * self.vp.pixel_size / 2.0 / (yfov / 2.0).tan();
+        asset.cameras.push(SimpleCamera {
+            setting: CameraSetting::with_up(
+                scale_point(eye, self.scale),
+                scale_point(lookat, self.scale),
+                scale_direction(up),
+                view_plane_distance,
+            ),
+        });
+    }
+
+    fn add_light(
+        &self,
+        asset: &mut Asset,
+        name: &str,
+        light: &::gltf::khr_lights_punctual::Light,
+        transform: &Matrix4<f64>,
+    ) {
+        let c = light.color();
+        let cl = Color::new(c[0] as f64, c[1] as f64, c[2] as f64);
+        let ls = light.intensity() as f64;
+        let location = scale_point(transform.transform_point(&Point3::origin()), self.scale);
+        if light.range().is_some() {
+            asset.warnings.push(format!(
+                "{}: light `{}` range ignored",
+                self.file_name, name
+            ));
+        }
+        match light.kind() {
+            Kind::Directional => {
+                // lights shine down -z, the direction points towards the light
+                let direction = transform.transform_vector(&Vec3::z()).normalize();
+                asset.lights.push(Arc::new(DirectionalLight {
+                    ls,
+                    cl,
+                    direction: scale_direction(direction),
+                }));
+            }
+            // punctual light intensities are in candela
+            Kind::Point => asset.lights.push(Arc::new(PointLight::new(
+                Intensity::Candela(ls),
+                cl,
+                location,
+                None,
+            ))),
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                let direction = transform.transform_vector(&-Vec3::z()).normalize();
+                asset.lights.push(Arc::new(SpotLight::new(
+                    Intensity::Candela(ls),
+                    cl,
+                    location,
+                    scale_direction(direction),
+                    inner_cone_angle as f64,
+                    outer_cone_angle as f64,
+                    None,
+                )));
+            }
+        }
+    }
+}
+
+fn gltf_error(file_name: &str, error: ::gltf::Error) -> SceneError {
+    match error {
+        ::gltf::Error::Io(e) if e.kind() == io::ErrorKind::NotFound => SceneError::MissingFile {
+            file: file_name.to_owned(),
+        },
+        ::gltf::Error::Deserialize(e) => SceneError::Parse {
+            file: file_name.to_owned(),
+            line: Some(e.line()),
+            message: e.to_string(),
+        },
+        e => SceneError::Parse {
+            file: file_name.to_owned(),
+            line: None,
+            message: e.to_string(),
+        },
+    }
+}
+
+// Blinn-Phong exponent with a similar highlight as the GGX roughness
+fn roughness_to_exponent(roughness: f64) -> f64 {
+    let alpha = (roughness * roughness).max(0.01);
+    (2.0 / (alpha * alpha) - 2.0).max(1.0)
+}
+
+fn to_point(p: [f32; 3]) -> Point3<f64> {
+    Point3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::asset::tests::{empty_asset, fixture};
+
+    fn vp() -> ViewPlane {
+        ViewPlane {
+            hres: 100,
+            vres: 100,
+            pixel_size: 1.0,
+        }
+    }
+
+    // a glTF file with one node drawing the positions, `node` adds to the node
+    // and `primitive` to its only primitive
+    fn gltf(name: &str, positions: &[[f32; 3]], node: &str, primitive: &str) -> String {
+        let buffer: Vec<u8> = positions
+            .iter()
+            .flatten()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let bin = fixture(&format!("{}.bin", name), &buffer);
+        let bin_name = Path::new(&bin).file_name().unwrap().to_string_lossy();
+        let gltf = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scene": 0,
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": [{{"mesh": 0 {node}}}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}} {primitive}}}]}}],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5126, "count": {count}, "type": "VEC3",
+                    "min": [-100, -100, -100], "max": [100, 100, 100]
+                }}],
+                "bufferViews": [{{"buffer": 0, "byteLength": {length}}}],
+                "buffers": [{{"uri": "{bin_name}", "byteLength": {length}}}]
+            }}"#,
+            node = node,
+            primitive = primitive,
+            count = positions.len(),
+            length = buffer.len(),
+            bin_name = bin_name,
+        );
+        fixture(&format!("{}.gltf", name), gltf.as_bytes())
+    }
+
+    #[test]
+    fn gltf_primitive_with_two_indices_is_skipped() {
+        // two vertices and no indices, so only two implied indices
+        let file = gltf("two-indices", &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]], "", "");
+        let mut asset = empty_asset();
+        asset.add_gltf(&file, 555.0, &vp()).unwrap();
+        assert!(asset.meshes.is_empty());
+        assert!(asset.warnings.iter().any(|w| w.contains("no faces")));
+    }
+
+    #[test]
+    fn node_transform_places_the_fan() {
+        let square = [
+            [0.0, 0.0, 0.0],
+            [10.0, 0.0, 0.0],
+            [10.0, 10.0, 0.0],
+            [0.0, 10.0, 0.0],
+        ];
+        let file = gltf(
+            "fan",
+            &square,
+            r#", "translation": [0, 0, 100]"#,
+            r#", "mode": 6"#,
+        );
+        let mut asset = empty_asset();
+        asset.add_gltf(&file, 555.0, &vp()).unwrap();
+        let mesh = &asset.meshes[0].mesh;
+        assert_eq!(mesh.faces, [[0, 1, 2], [0, 2, 3]]);
+        let expected = scale_point(Point3::new(10.0, 10.0, 100.0), 555.0);
+        assert!((mesh.positions[2] - expected).norm() < 1e-9);
+        // without a material the primitive is white matte
+        assert!(matches!(
+            **asset.materials.get(&mesh.material_id).unwrap(),
+            Material::Matte(_)
+        ));
+    }
+
+    #[test]
+    fn unknown_primitive_mode_is_skipped() {
+        let line = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]];
+        let file = gltf("lines", &line, "", r#", "mode": 1"#);
+        let mut asset = empty_asset();
+        asset.add_gltf(&file, 555.0, &vp()).unwrap();
+        assert!(asset.meshes.is_empty());
+        assert!(asset.warnings[0].contains("mode Lines ignored"));
+    }
+
+    #[test]
+    fn missing_gltf_is_reported() {
+        let file = std::env::temp_dir().join("raytracing-nowhere.gltf");
+        let error = empty_asset().add_gltf(&file.to_string_lossy(), 555.0, &vp());
+        assert!(matches!(error, Err(SceneError::MissingFile { .. })));
+    }
+
+    #[test]
+    fn rougher_surfaces_have_broader_highlights() {
+        assert!((roughness_to_exponent(1.0) - 1.0).abs() < 1e-12);
+        assert!(roughness_to_exponent(0.2) > roughness_to_exponent(0.5));
+        // a perfect mirror is capped rather than infinite
+        assert!(roughness_to_exponent(0.0).is_finite());
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::hdr::HdrDecoder;
+use image::ImageError;
+
+use crate::error::SceneError;
+
+// linear RGB pixels in rows
+pub struct HdrImage {
+    pub pixels: Vec<[f32; 3]>,
+    pub width: usize,
+}
+
+// Radiance .hdr files are read as they are, other images are taken to be
+// sRGB encoded and converted to linear
+pub fn load_hdr(file_name: &str) -> Result<HdrImage, SceneError> {
+    let parse_error = |e: ImageError| SceneError::Parse {
+        file: file_name.to_owned(),
+        line: None,
+        message: e.to_string(),
+    };
+    let missing = || SceneError::MissingFile {
+        file: file_name.to_owned(),
+    };
+    let extension = Path::new(file_name).extension();
+    if matches!(extension, Some(ext) if ext.eq_ignore_ascii_case("exr")) {
+        return Err(SceneError::Parse {
+            file: file_name.to_owned(),
+            line: None,
+            message: "OpenEXR is not supported, convert the image to .hdr".to_owned(),
+        });
+    }
+    if matches!(extension, Some(ext) if ext.eq_ignore_ascii_case("hdr")) {
+        let file = File::open(file_name).map_err(|_| missing())?;
+        let decoder = HdrDecoder::new(BufReader::new(file)).map_err(parse_error)?;
+        let width = decoder.metadata().width as usize;
+        let pixels = decoder.read_image_hdr().map_err(parse_error)?;
+        return Ok(HdrImage {
+            pixels: pixels.iter().map(|p| p.0).collect(),
+            width,
+        });
+    }
+    let image = image::open(file_name).map_err(|e| match e {
+        ImageError::IoError(_) => missing(),
+        e => parse_error(e),
+    })?;
+    let rgb = image.to_rgb16();
+    Ok(HdrImage {
+        width: rgb.width() as usize,
+        pixels: rgb
+            .pixels()
+            .map(|p| p.0.map(|c| srgb_to_linear(c as f32 / u16::MAX as f32)))
+            .collect(),
+    })
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use image::ImageError;
+
+use crate::asset::load_hdr;
+use crate::error::SceneError;
+
+// samples of a grayscale image in rows along x
+pub struct Heightmap {
+    pub heights: Vec<f32>,
+    pub width: usize,
+}
+
+// 8 and 16 bit images map to [0, 1], Radiance .hdr files keep their float
+// values and use the first channel
+pub fn load_heightmap(file_name: &str) -> Result<Heightmap, SceneError> {
+    let extension = Path::new(file_name).extension();
+    if matches!(extension, Some(ext) if ext.eq_ignore_ascii_case("hdr")) {
+        let image = load_hdr(file_name)?;
+        return Ok(Heightmap {
+            heights: image.pixels.iter().map(|p| p[0]).collect(),
+            width: image.width,
+        });
+    }
+    let image = image::open(file_name).map_err(|e| match e {
+        ImageError::IoError(_) => SceneError::MissingFile {
+            file: file_name.to_owned(),
+        },
+        e => SceneError::Parse {
+            file: file_name.to_owned(),
+            line: None,
+            message: e.to_string(),
+        },
+    })?;
+    let gray = image.to_luma16();
+    Ok(Heightmap {
+        width: gray.width() as usize,
+        heights: gray
+            .into_raw()
+            .into_iter()
+            .map(|h| h as f32 / u16::MAX as f32)
+            .collect(),
+    })
+}
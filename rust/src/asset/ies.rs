@@ -0,0 +1,174 @@
+use std::fs;
+
+use crate::error::SceneError;
+use crate::light::IesProfile;
+
+// IES LM-63 photometric data. Keywords before the TILT line are skipped,
+// as are tilt factors, and candela are scaled by the multiplier and ballast
+// factor. Only type C photometry with TILT=NONE or INCLUDE is read
+pub fn load_ies(file_name: &str) -> Result<IesProfile, SceneError> {
+    let text = fs::read_to_string(file_name).map_err(|_| SceneError::MissingFile {
+        file: file_name.to_owned(),
+    })?;
+    parse_ies(file_name, &text)
+}
+
+pub fn parse_ies(file_name: &str, text: &str) -> Result<IesProfile, SceneError> {
+    let parse_error = |line: Option<usize>, message: &str| SceneError::Parse {
+        file: file_name.to_owned(),
+        line,
+        message: message.to_owned(),
+    };
+    let (tilt_line, tilt) = text
+        .lines()
+        .enumerate()
+        .find_map(|(i, line)| Some((i, line.trim().strip_prefix("TILT=")?)))
+        .ok_or_else(|| parse_error(None, "missing TILT line"))?;
+    let mut numbers = text
+        .lines()
+        .skip(tilt_line + 1)
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| parse_error(None, &format!("expected a number, found `{}`", token)))
+        });
+    let mut next = || {
+        numbers
+            .next()
+            .unwrap_or_else(|| Err(parse_error(None, "unexpected end of file")))
+    };
+    let count = |x: f64, what: &str| {
+        if x >= 0.0 && x.fract() == 0.0 && x <= f64::from(u32::MAX) {
+            Ok(x as usize)
+        } else {
+            Err(parse_error(None, &format!("invalid {} `{}`", what, x)))
+        }
+    };
+    match tilt.trim() {
+        "NONE" => {}
+        "INCLUDE" => {
+            // lamp to luminaire geometry, then pairs of angles and factors
+            next()?;
+            let pairs = count(next()?, "number of tilt angles")?;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+        _ => {
+            return Err(parse_error(
+                Some(tilt_line + 1),
+                "tilt data in another file is not supported",
+            ))
+        }
+    }
+    let _lamps = next()?;
+    let _lumens_per_lamp = next()?;
+    let multiplier = next()?;
+    let n_vertical = count(next()?, "number of vertical angles")?;
+    let n_horizontal = count(next()?, "number of horizontal angles")?;
+    let photometric_type = next()?;
+    // units, width, length and height
+    for _ in 0..4 {
+        next()?;
+    }
+    let ballast_factor = next()?;
+    // ballast lamp photometric factor and input watts
+    next()?;
+    next()?;
+    if photometric_type as i32 != 1 {
+        return Err(parse_error(None, "only type C photometry is supported"));
+    }
+    if n_vertical == 0 || n_horizontal == 0 {
+        return Err(parse_error(None, "no angles"));
+    }
+    let vertical = (0..n_vertical)
+        .map(|_| next())
+        .collect::<Result<Vec<f64>, _>>()?;
+    let horizontal = (0..n_horizontal)
+        .map(|_| next())
+        .collect::<Result<Vec<f64>, _>>()?;
+    let n_candela = n_vertical
+        .checked_mul(n_horizontal)
+        .ok_or_else(|| parse_error(None, "too many angles"))?;
+    let candela = (0..n_candela)
+        .map(|_| next().map(|c| c * multiplier * ballast_factor))
+        .collect::<Result<Vec<f64>, _>>()?;
+    let increasing = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+    if !increasing(&vertical) || !increasing(&horizontal) {
+        return Err(parse_error(None, "angles must increase"));
+    }
+    // the symmetries type C photometry can describe, see IesProfile
+    let (first, last) = (horizontal[0], horizontal[n_horizontal - 1]);
+    let symmetric = (first == 0.0 && [0.0, 90.0, 180.0, 360.0].contains(&last))
+        || (first == 90.0 && last == 270.0);
+    if !symmetric {
+        return Err(parse_error(
+            None,
+            &format!(
+                "horizontal angles from {} to {} are not supported",
+                first, last
+            ),
+        ));
+    }
+    Ok(IesProfile::new(vertical, horizontal, candela))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ies(tilt: &str, counts: &str) -> String {
+        format!(
+            "IESNA:LM-63-2002\nTILT={}\n1 1000 1 {} 1 1 0 0 0\n1 1 100\n0 90\n0\n100 50\n",
+            tilt, counts
+        )
+    }
+
+    #[test]
+    fn parses_a_profile() {
+        let profile = parse_ies("lamp.ies", &ies("NONE", "2 1")).unwrap();
+        assert_eq!(profile.max_candela(), 100.0);
+    }
+
+    #[test]
+    fn rejects_invalid_counts() {
+        for counts in ["-2 1", "2.5 1", "4294967295 4294967295"] {
+            let result = parse_ies("lamp.ies", &ies("NONE", counts));
+            assert!(
+                matches!(result, Err(SceneError::Parse { .. })),
+                "{}",
+                counts
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_tilt_file() {
+        let result = parse_ies("lamp.ies", &ies("tilt.dat", "2 1"));
+        assert!(matches!(
+            result,
+            Err(SceneError::Parse { line: Some(2), .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_horizontal_ranges() {
+        for horizontal in ["0 120", "45 90", "90 180"] {
+            let text = format!(
+                "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 1 2 1 1 0 0 0\n1 1 100\n0\n{}\n100 50\n",
+                horizontal
+            );
+            let result = parse_ies("lamp.ies", &text);
+            assert!(
+                matches!(result, Err(SceneError::Parse { .. })),
+                "{}",
+                horizontal
+            );
+        }
+        let text =
+            "IESNA:LM-63-2002\nTILT=NONE\n1 1000 1 1 2 1 1 0 0 0\n1 1 100\n0\n90 270\n100 50\n";
+        assert!(parse_ies("lamp.ies", text).is_ok());
+    }
+}
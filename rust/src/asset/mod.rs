@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_6;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use nalgebra::Point3;
+use tobj::{load_obj, LoadError, LoadOptions};
+
+use crate::brdf::{GlossySpecular, Lambertian, PerfectSpecular};
+use crate::camera::SimpleCamera;
+use crate::color::Color;
+use crate::error::SceneError;
+use crate::geometric_object::{
+    Disk, Geometry, Heightfield, MeshBvh, Rectangle, Sphere, TriangleMesh,
+};
+use crate::light::{
+    AreaLight, Background, DiskLight, EnvironmentLight, Light, RectangleLight, SphereLight,
+    SunPosition, SunSky,
+};
+use crate::material::{Emissive, Material, Matte, Reflective};
+use crate::model::Vec3;
+
+pub mod gltf;
+pub mod hdr;
+pub mod heightmap;
+pub mod ies;
+pub mod mtl;
+pub mod ply;
+pub mod stl;
+
+pub use hdr::*;
+pub use heightmap::*;
+pub use ies::*;
+pub use mtl::*;
+pub use ply::*;
+pub use stl::*;
+
+pub struct Asset {
+    pub meshes: Vec<Arc<MeshBvh>>, // shared by instances of the mesh
+    pub geometries: Vec<Arc<dyn Geometry + Send + Sync>>,
+    pub lights: Vec<Arc<dyn Light + Send + Sync>>,
+    pub cameras: Vec<SimpleCamera>,
+    pub materials: HashMap<usize, Box<Material>>,
+    pub background: Option<Arc<dyn Background + Send + Sync>>,
+    pub warnings: Vec<String>,
+}
+
+impl Asset {
+    pub fn new(file_name: &str) -> Result<Asset, SceneError> {
+        let mut asset = Asset {
+            meshes: vec![],
+            geometries: vec![],
+            lights: vec![],
+            cameras: vec![],
+            materials: HashMap::new(),
+            background: None,
+            warnings: vec![],
+        };
+
+        let (models, materials) = load_obj(
+            file_name,
+            &LoadOptions {
+                triangulate: true,
+                ..LoadOptions::default()
+            },
+        )
+        .map_err(|e| obj_error(file_name, &e))?;
+
+        let materials = materials.map_err(|e| mtl_error(file_name, &e))?;
+        check_material_names(file_name, &materials)?;
+        let scale = 555.0;
+
+        for (material_id, m) in materials.iter().enumerate() {
+            let material = translate_material(m, &mut asset.warnings);
+            asset.materials.insert(material_id, Box::new(material));
+        }
+        // meshes without `usemtl` share a plain white material
+        let default_material_id = materials.len();
+
+        for model in &models {
+            let mesh = &model.mesh;
+            let positions = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Point3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                .collect();
+            let faces = mesh
+                .indices
+                .chunks_exact(3)
+                .map(|f| [f[0], f[1], f[2]])
+                .collect();
+
+            let material_id = match mesh.material_id {
+                Some(material_id) => material_id,
+                None => {
+                    asset.warnings.push(format!(
+                        "{}: object `{}` has no material, using white matte",
+                        file_name, model.name
+                    ));
+                    asset
+                        .materials
+                        .entry(default_material_id)
+                        .or_insert_with(|| Box::new(white_matte()));
+                    default_material_id
+                }
+            };
+
+            let mesh = TriangleMesh::new(material_id, positions, faces, scale);
+            asset.add_mesh(mesh, file_name, &format!("object `{}`", model.name))?;
+        }
+
+        let material = Material::Reflective(Reflective::new(
+            Lambertian::new(0.1, Color::new(1.0, 1.0, 1.0)),
+            Lambertian::new(0.1, Color::new(1.0, 1.0, 1.0)),
+            GlossySpecular::new(0.2, 2.0),
+            PerfectSpecular::new(0.5, Color::new(1.0, 1.0, 1.0)),
+        ));
+        let material_id = 1000_usize;
+        asset.geometries.push(Arc::new(Sphere::new(
+            material_id,
+            40.0,
+            Point3::new(400.0, 40.0, 55.0),
+            scale,
+        )));
+        asset.materials.insert(material_id, Box::new(material));
+        Ok(asset)
+    }
+
+    // adds a PLY mesh rendered with the given material
+    pub fn add_ply(
+        &mut self,
+        file_name: &str,
+        material: Material,
+        scale: f64,
+    ) -> Result<(), SceneError> {
+        let mesh = load_ply(file_name)?;
+        let material_id = self.next_material_id();
+        self.materials.insert(material_id, Box::new(material));
+        let mut triangle_mesh = TriangleMesh::new(material_id, mesh.positions, mesh.faces, scale);
+        if let Some(normals) = mesh.normals {
+            triangle_mesh = triangle_mesh.with_normals(normals);
+        }
+        if let Some(uvs) = mesh.uvs {
+            triangle_mesh = triangle_mesh.with_uvs(uvs);
+        }
+        if let Some(colors) = mesh.colors {
+            triangle_mesh = triangle_mesh.with_colors(colors);
+        }
+        self.add_mesh(triangle_mesh, file_name, "mesh")
+    }
+
+    // Adds an STL part rendered with the given material. Welding merges vertices
+    // within the tolerance and shades the part with smooth normals
+    pub fn add_stl(
+        &mut self,
+        file_name: &str,
+        material: Material,
+        scale: f64,
+        weld_tolerance: Option<f64>,
+    ) -> Result<(), SceneError> {
+        let mut mesh = load_stl(file_name)?;
+        if let Some(tolerance) = weld_tolerance {
+            let welded =
+                weld(&mesh, tolerance).map_err(|message| SceneError::DegenerateGeometry {
+                    file: Some(file_name.to_owned()),
+                    message,
+                })?;
+            mesh = smooth_normals(&welded, FRAC_PI_6);
+        }
+        let material_id = self.next_material_id();
+        self.materials.insert(material_id, Box::new(material));
+        let faces = mesh
+            .faces
+            .iter()
+            .map(|face| face.map(|i| i as u32))
+            .collect();
+        let mut triangle_mesh = TriangleMesh::new(material_id, mesh.positions, faces, scale);
+        if let Some(normals) = mesh.normals {
+            triangle_mesh = triangle_mesh.with_normals(normals);
+        }
+        self.add_mesh(triangle_mesh, file_name, "part")
+    }
+
+    // Adds a terrain from a grayscale image. `origin` is the corner in scene
+    // units, `size` the extent along x and z and the height of a sample of 1
+    pub fn add_heightfield(
+        &mut self,
+        file_name: &str,
+        material: Material,
+        origin: Point3<f64>,
+        size: Vec3,
+        scale: f64,
+    ) -> Result<(), SceneError> {
+        let map = load_heightmap(file_name)?;
+        let material_id = self.next_material_id();
+        self.materials.insert(material_id, Box::new(material));
+        let heightfield =
+            Heightfield::new(material_id, map.heights, map.width, origin, size, scale)
+                .map_err(|e| e.with_file(file_name))?;
+        self.geometries.push(Arc::new(heightfield));
+        Ok(())
+    }
+
+    // Lights the scene from an equirectangular image, which also becomes the
+    // background. `rotation` turns it about the up axis, in radians
+    pub fn add_environment(
+        &mut self,
+        file_name: &str,
+        rotation: f64,
+        intensity: f64,
+    ) -> Result<(), SceneError> {
+        let image = load_hdr(file_name)?;
+        let light = EnvironmentLight::new(image.pixels, image.width, rotation, intensity)
+            .map_err(|e| e.with_file(file_name))?;
+        let light = Arc::new(light);
+        self.lights.push(light.clone());
+        self.background = Some(light);
+        Ok(())
+    }
+
+    // Daylight for the time and place, which also becomes the background
+    pub fn add_sun_sky(&mut self, position: &SunPosition, turbidity: f64, intensity: f64) {
+        let light = Arc::new(SunSky::new(position, turbidity, intensity));
+        self.lights.push(light.clone());
+        self.background = Some(light);
+    }
+
+    // Emitting sphere, disk and rectangle, both visible and sampled as lights.
+    // Positions and sizes are in scene units
+    pub fn add_sphere_light(
+        &mut self,
+        center: Point3<f64>,
+        radius: f64,
+        emissive: Emissive,
+        scale: f64,
+    ) {
+        let material_id = self.add_emissive(&emissive);
+        let sphere = Arc::new(Sphere::new(material_id, radius, center, scale));
+        self.geometries.push(sphere.clone());
+        self.lights
+            .push(Arc::new(SphereLight::new(sphere, emissive)));
+    }
+
+    pub fn add_disk_light(
+        &mut self,
+        center: Point3<f64>,
+        normal: Vec3,
+        radius: f64,
+        emissive: Emissive,
+        scale: f64,
+    ) {
+        let material_id = self.add_emissive(&emissive);
+        let disk = Arc::new(Disk::new(material_id, center, normal, radius, scale));
+        self.geometries.push(disk.clone());
+        self.lights.push(Arc::new(DiskLight::new(disk, emissive)));
+    }
+
+    pub fn add_rectangle_light(
+        &mut self,
+        p0: Point3<f64>,
+        a: Vec3,
+        b: Vec3,
+        emissive: Emissive,
+        scale: f64,
+    ) {
+        let material_id = self.add_emissive(&emissive);
+        let rectangle = Arc::new(Rectangle::new(material_id, p0, a, b, scale));
+        self.geometries.push(rectangle.clone());
+        self.lights
+            .push(Arc::new(RectangleLight::new(rectangle, emissive)));
+    }
+
+    fn add_emissive(&mut self, emissive: &Emissive) -> usize {
+        let material_id = self.next_material_id();
+        let material = Material::Emissive(Emissive::new(emissive.ls, emissive.ce));
+        self.materials.insert(material_id, Box::new(material));
+        material_id
+    }
+
+    pub fn next_material_id(&self) -> usize {
+        self.materials.keys().max().map_or(0, |id| id + 1)
+    }
+
+    // Emissive meshes also become area lights, the material must already be added.
+    // `name` describes the mesh in errors and warnings. Faces without area are
+    // dropped and meshes left without faces are skipped
+    fn add_mesh(
+        &mut self,
+        mut mesh: TriangleMesh,
+        file_name: &str,
+        name: &str,
+    ) -> Result<(), SceneError> {
+        if let Some(f) = mesh.find_face_out_of_range() {
+            return Err(SceneError::DegenerateGeometry {
+                file: Some(file_name.to_owned()),
+                message: format!("{} face {} refers to a missing vertex", name, f),
+            });
+        }
+        let dropped = mesh.remove_faces_without_area();
+        if dropped > 0 {
+            self.warnings.push(format!(
+                "{}: {} has {} faces without area, skipped",
+                file_name, name, dropped
+            ));
+        }
+        if mesh.faces.is_empty() {
+            self.warnings
+                .push(format!("{}: {} has no faces, skipped", file_name, name));
+            return Ok(());
+        }
+        let mesh = Arc::new(mesh);
+        let material_id = mesh.material_id;
+        if let Some(Material::Emissive(emissive)) = self.materials.get(&material_id).map(|m| &**m) {
+            let emissive = Emissive::new(emissive.ls, emissive.ce);
+            let arealight =
+                AreaLight::new(mesh.triangles(), emissive).map_err(|e| e.with_file(file_name))?;
+            self.lights.push(Arc::new(arealight));
+        }
+        let bvh = Arc::new(MeshBvh::new(mesh));
+        self.geometries.push(bvh.clone());
+        self.meshes.push(bvh);
+        Ok(())
+    }
+}
+
+fn white_matte() -> Material {
+    Material::Matte(Matte::new(
+        Lambertian::new(0.5, Color::zeros()),
+        Lambertian::new(1.0, Color::repeat(1.0)),
+    ))
+}
+
+fn obj_error(file_name: &str, error: &LoadError) -> SceneError {
+    if matches!(error, LoadError::OpenFileFailed) && !Path::new(file_name).exists() {
+        return SceneError::MissingFile {
+            file: file_name.to_owned(),
+        };
+    }
+    SceneError::Parse {
+        file: file_name.to_owned(),
+        line: locate_obj_error(file_name, error),
+        message: error.to_string(),
+    }
+}
+
+// tobj does not report where parsing failed, look for the first malformed statement instead
+fn locate_obj_error(file_name: &str, error: &LoadError) -> Option<usize> {
+    let (keyword, arity) = match error {
+        LoadError::PositionParseError => ("v", 3),
+        LoadError::NormalParseError => ("vn", 3),
+        LoadError::TexcoordParseError => ("vt", 1),
+        LoadError::FaceParseError | LoadError::InvalidPolygon => ("f", 3),
+        _ => return None,
+    };
+    let content = fs::read_to_string(file_name).ok()?;
+    let malformed = obj_statements(&content, keyword)
+        .find(|(_, statement)| {
+            let words: Vec<_> = statement.split_whitespace().collect();
+            let well_formed = if keyword == "f" {
+                words.iter().all(|w| {
+                    w.split('/').enumerate().all(|(i, index)| {
+                        (i > 0 && index.is_empty()) || index.parse::<isize>().is_ok()
+                    })
+                })
+            } else {
+                words.iter().all(|w| w.parse::<f64>().is_ok())
+            };
+            words.len() < arity || !well_formed
+        })
+        .map(|(line, _)| line);
+    malformed
+}
+
+// (line number, rest of the line) of every statement starting with keyword
+fn obj_statements<'a>(
+    content: &'a str,
+    keyword: &'a str,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    content.lines().enumerate().filter_map(move |(i, line)| {
+        let line = line.trim();
+        line.strip_prefix(keyword)
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(|rest| (i + 1, rest.trim()))
+    })
+}
+
+// a library that isn't there, or else the first one, which tobj failed to parse
+fn mtl_error(file_name: &str, error: &LoadError) -> SceneError {
+    let content = fs::read_to_string(file_name).unwrap_or_default();
+    let libraries: Vec<_> = obj_statements(&content, "mtllib").collect();
+    let path = |mtl: &str| Path::new(file_name).with_file_name(mtl);
+    match libraries.iter().find(|(_, mtl)| !path(mtl).exists()) {
+        Some((line, mtl)) => SceneError::MissingMtl {
+            file: file_name.to_owned(),
+            line: Some(*line),
+            mtl: (*mtl).to_owned(),
+        },
+        None => SceneError::Parse {
+            file: libraries.first().map_or(file_name.to_owned(), |(_, mtl)| {
+                path(mtl).to_string_lossy().into_owned()
+            }),
+            line: None,
+            message: error.to_string(),
+        },
+    }
+}
+
+// tobj silently drops `usemtl` statements naming a material the library doesn't define
+fn check_material_names(file_name: &str, materials: &[tobj::Material]) -> Result<(), SceneError> {
+    let content = fs::read_to_string(file_name).unwrap_or_default();
+    let unknown = obj_statements(&content, "usemtl")
+        .find(|(_, name)| materials.iter().all(|m| m.name != *name));
+    match unknown {
+        Some((line, name)) => Err(SceneError::UnknownMaterial {
+            file: Some(file_name.to_owned()),
+            line: Some(line),
+            material: name.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn empty_asset() -> Asset {
+        Asset {
+            meshes: vec![],
+            geometries: vec![],
+            lights: vec![],
+            cameras: vec![],
+            materials: HashMap::new(),
+            background: None,
+            warnings: vec![],
+        }
+    }
+
+    // writes a fixture into the temporary directory, named after the test
+    pub fn fixture(name: &str, content: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("raytracing-{}", name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn obj_object_without_faces_is_skipped() {
+        let file = fixture("no-faces.obj", b"o empty\nv 0 0 0\nv 1 0 0\nv 0 1 0\n");
+        let asset = Asset::new(&file).unwrap();
+        assert!(asset.meshes.is_empty());
+        assert!(asset.warnings.iter().any(|w| w.contains("no faces")));
+    }
+
+    #[test]
+    fn faces_without_area_are_dropped() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        let mesh = TriangleMesh::new(0, positions.clone(), vec![[0, 1, 2], [0, 1, 1]], 555.0);
+        let mut asset = empty_asset();
+        asset.add_mesh(mesh, "mesh.obj", "mesh").unwrap();
+        assert_eq!(asset.meshes[0].mesh.faces.len(), 1);
+        assert!(asset.warnings[0].contains("1 faces without area"));
+
+        let mesh = TriangleMesh::new(0, positions, vec![[0, 1, 3]], 555.0);
+        let error = empty_asset().add_mesh(mesh, "mesh.obj", "mesh");
+        assert!(matches!(error, Err(SceneError::DegenerateGeometry { .. })));
+    }
+
+    #[test]
+    fn missing_mtl_is_named() {
+        let file = fixture(
+            "missing-mtl.obj",
+            b"mtllib nowhere.mtl
+v 0 0 0
+",
+        );
+        match Asset::new(&file) {
+            Err(SceneError::MissingMtl { line, mtl, .. }) => {
+                assert_eq!(line, Some(1));
+                assert_eq!(mtl, "nowhere.mtl");
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn missing_obj_is_reported() {
+        let file = std::env::temp_dir().join("raytracing-nowhere.obj");
+        match Asset::new(&file.to_string_lossy()) {
+            Err(SceneError::MissingFile { file: missing }) => {
+                assert_eq!(missing, file.to_string_lossy());
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn malformed_statement_is_located() {
+        let file = fixture("malformed.obj", b"v 0 0 0\nv 1 0 0\nv 0 one 0\nf 1 2 3\n");
+        match Asset::new(&file) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, Some(3)),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn unknown_usemtl_is_named() {
+        fixture("known.mtl", b"newmtl known\nKd 1 1 1\n");
+        let file = fixture(
+            "unknown-usemtl.obj",
+            b"mtllib raytracing-known.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl unknown\nf 1 2 3\n",
+        );
+        match Asset::new(&file) {
+            Err(SceneError::UnknownMaterial { line, material, .. }) => {
+                assert_eq!(line, Some(5));
+                assert_eq!(material, "unknown");
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn area_lights_share_scene_units() {
+        let center = Point3::new(100.0, 200.0, 300.0);
+        let emissive = || Emissive::new(1.0, Color::repeat(1.0));
+        let mut asset = empty_asset();
+        asset.add_sphere_light(center, 10.0, emissive(), 555.0);
+        asset.add_disk_light(center, Vec3::y(), 10.0, emissive(), 555.0);
+        let (a, b) = (Vec3::new(20.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 20.0));
+        asset.add_rectangle_light(center - (a + b) / 2.0, a, b, emissive(), 555.0);
+        let sphere = asset.geometries[0].get_center();
+        for geometry in &asset.geometries[1..] {
+            assert!((geometry.get_center() - sphere).norm() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::brdf::{GlossySpecular, Lambertian, PerfectSpecular};
+use crate::btdf::PerfectTransmitter;
+use crate::color::Color;
+use crate::material::{Emissive, Material, Matte, Phong, Reflective, Transparent};
+
+// Maps an MTL material and its illumination model onto the closest material
+// this renderer supports, see http://paulbourke.net/dataformats/mtl/
+pub fn translate_material(m: &tobj::Material, warnings: &mut Vec<String>) -> Material {
+    warn_unsupported_fields(m, warnings);
+
+    if let Some(emissive) = emission(m, warnings) {
+        return Material::Emissive(emissive);
+    }
+
+    let illum = m.illumination_model;
+    let dissolve = match m
+        .unknown_param
+        .get("Tr")
+        .and_then(|tr| tr.trim().parse::<f64>().ok())
+    {
+        Some(tr) if m.dissolve >= 1.0 => 1.0 - tr,
+        _ => m.dissolve as f64,
+    };
+    if dissolve < 1.0 && matches!(illum, Some(0..=3 | 5 | 8)) {
+        warnings.push(format!(
+            "material `{}`: dissolve {} ignored by illum {}",
+            m.name,
+            dissolve,
+            illum.unwrap_or_default()
+        ));
+    }
+
+    match illum {
+        Some(0 | 1) => matte(m),
+        Some(2) => phong(m),
+        Some(3 | 5 | 8) => reflective(m),
+        Some(4 | 6 | 7 | 9) => transparent(m, dissolve),
+        _ => {
+            if let Some(illum) = illum {
+                warnings.push(format!(
+                    "material `{}`: illum {} is not supported",
+                    m.name, illum
+                ));
+            }
+            if dissolve < 1.0 {
+                transparent(m, dissolve)
+            } else {
+                phong(m)
+            }
+        }
+    }
+}
+
+fn matte(m: &tobj::Material) -> Material {
+    let ambient_brdf = Lambertian::new(0.5, to_color(m.ambient));
+    let diffuse_brdf = Lambertian::new(1.0, to_color(m.diffuse));
+    Material::Matte(Matte::new(ambient_brdf, diffuse_brdf))
+}
+
+// falls back to matte when there is no specular highlight
+fn phong(m: &tobj::Material) -> Material {
+    let ks = to_color(m.specular).max().min(0.99);
+    if ks <= 0.0 {
+        return matte(m);
+    }
+    Material::Phong(Phong::new(
+        Lambertian::new(0.5, to_color(m.ambient)),
+        Lambertian::new((1.0 - ks) * 0.99, to_color(m.diffuse)),
+        GlossySpecular::new(ks, shininess(m)),
+    ))
+}
+
+fn reflective(m: &tobj::Material) -> Material {
+    let specular = to_color(m.specular);
+    let kr = specular.max().min(1.0);
+    let cr = if kr > 0.0 { specular / kr } else { specular };
+    Material::Reflective(Reflective::new(
+        Lambertian::new(0.5, to_color(m.ambient)),
+        Lambertian::new(1.0 - kr, to_color(m.diffuse)),
+        GlossySpecular::new(kr, shininess(m)),
+        PerfectSpecular::new(kr, cr),
+    ))
+}
+
+fn transparent(m: &tobj::Material, dissolve: f64) -> Material {
+    let specular = to_color(m.specular);
+    let kr = specular.max().clamp(0.0, 1.0);
+    let kr = if kr > 0.0 { kr } else { 0.1 };
+    let kt = m
+        .unknown_param
+        .get("Tf")
+        .and_then(|tf| parse_color(tf))
+        .map_or(
+            if dissolve < 1.0 {
+                1.0 - dissolve
+            } else {
+                1.0 - kr
+            },
+            |tf| tf.max(),
+        )
+        .clamp(0.0, 1.0 - kr);
+    Material::Transparent(Transparent::new(
+        Lambertian::new(0.5, to_color(m.ambient)),
+        Lambertian::new(1.0 - kr - kt, to_color(m.diffuse)),
+        GlossySpecular::new(kr, shininess(m)),
+        PerfectSpecular::new(kr, Color::repeat(1.0)),
+        PerfectTransmitter::new(kt, m.optical_density as f64),
+    ))
+}
+
+// Ke is the emission, older files abuse an ambient red channel above 1 instead
+fn emission(m: &tobj::Material, warnings: &mut Vec<String>) -> Option<Emissive> {
+    if let Some(ke) = m.unknown_param.get("Ke") {
+        return match parse_color(ke) {
+            Some(ke) if ke.max() > 0.0 => Some(Emissive::new(ke.max(), ke / ke.max())),
+            Some(_) => None,
+            None => {
+                warnings.push(format!("material `{}`: invalid Ke `{}`", m.name, ke));
+                None
+            }
+        };
+    }
+    if m.ambient[0] > 1.0 {
+        warnings.push(format!(
+            "material `{}`: treating Ka > 1 as emission, use Ke instead",
+            m.name
+        ));
+        return Some(Emissive::new(m.ambient[0] as f64, to_color(m.diffuse)));
+    }
+    None
+}
+
+fn warn_unsupported_fields(m: &tobj::Material, warnings: &mut Vec<String>) {
+    let textures = [
+        ("map_Ka", &m.ambient_texture),
+        ("map_Kd", &m.diffuse_texture),
+        ("map_Ks", &m.specular_texture),
+        ("map_Bump", &m.normal_texture),
+        ("map_Ns", &m.shininess_texture),
+        ("map_d", &m.dissolve_texture),
+    ];
+    for (field, texture) in textures {
+        if !texture.is_empty() {
+            warnings.push(format!(
+                "material `{}`: {} `{}` ignored, textures are not supported",
+                m.name, field, texture
+            ));
+        }
+    }
+    let mut unknown: Vec<_> = m
+        .unknown_param
+        .keys()
+        .filter(|k| !matches!(k.as_str(), "Ke" | "Tf" | "Tr"))
+        .collect();
+    unknown.sort();
+    for field in unknown {
+        warnings.push(format!(
+            "material `{}`: unsupported field {} ignored",
+            m.name, field
+        ));
+    }
+}
+
+fn shininess(m: &tobj::Material) -> f64 {
+    (m.shininess as f64).max(1.0)
+}
+
+fn to_color(c: [f32; 3]) -> Color {
+    Color::new(c[0] as f64, c[1] as f64, c[2] as f64)
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let values = value
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<f64>, _>>()
+        .ok()?;
+    match values[..] {
+        [c] => Some(Color::repeat(c)),
+        [r, g, b] => Some(Color::new(r, g, b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(mtl: &str) -> (Material, Vec<String>) {
+        let (materials, _) = tobj::load_mtl_buf(&mut mtl.as_bytes()).unwrap();
+        let mut warnings = vec![];
+        (translate_material(&materials[0], &mut warnings), warnings)
+    }
+
+    #[test]
+    fn illumination_models_pick_the_material() {
+        let (m, _) = translate("newmtl m\nKd 1 0 0\nillum 1\n");
+        assert!(matches!(m, Material::Matte(_)));
+        let (m, _) = translate("newmtl m\nKs 0.5 0.5 0.5\nNs 20\nillum 2\n");
+        assert!(matches!(m, Material::Phong(_)));
+        // no highlight to speak of
+        let (m, _) = translate("newmtl m\nKs 0 0 0\nillum 2\n");
+        assert!(matches!(m, Material::Matte(_)));
+        let (m, _) = translate("newmtl m\nKs 0.8 0.4 0.4\nillum 3\n");
+        match m {
+            Material::Reflective(m) => {
+                assert!((m.reflective_brdf.kr - 0.8).abs() < 1e-6);
+                assert!((m.reflective_brdf.cr - Color::new(1.0, 0.5, 0.5)).norm() < 1e-6);
+            }
+            _ => panic!("expected a reflective material"),
+        }
+        let (m, _) = translate("newmtl m\nKs 0.1 0.1 0.1\nNi 1.5\nillum 7\n");
+        match m {
+            Material::Transparent(m) => {
+                assert!((m.specular_btdf.ior - 1.5).abs() < 1e-6);
+                assert!((m.specular_btdf.kt - 0.9).abs() < 1e-6);
+            }
+            _ => panic!("expected a transparent material"),
+        }
+    }
+
+    #[test]
+    fn unsupported_illumination_model_warns() {
+        let (m, warnings) = translate("newmtl m\nKs 0.5 0.5 0.5\nillum 10\n");
+        assert!(matches!(m, Material::Phong(_)));
+        assert_eq!(warnings, ["material `m`: illum 10 is not supported"]);
+        let (m, _) = translate("newmtl m\nd 0.25\nillum 10\n");
+        assert!(matches!(m, Material::Transparent(_)));
+    }
+
+    #[test]
+    fn tr_is_the_complement_of_dissolve() {
+        let (m, _) = translate("newmtl m\nKs 0.1 0.1 0.1\nTr 0.75\nillum 4\n");
+        match m {
+            Material::Transparent(m) => assert!((m.specular_btdf.kt - 0.75).abs() < 1e-6),
+            _ => panic!("expected a transparent material"),
+        }
+        let (m, warnings) = translate("newmtl m\nKs 0.5 0.5 0.5\nd 0.5\nillum 2\n");
+        assert!(matches!(m, Material::Phong(_)));
+        assert_eq!(warnings, ["material `m`: dissolve 0.5 ignored by illum 2"]);
+    }
+
+    #[test]
+    fn emission_comes_from_ke_or_a_bright_ka() {
+        let (m, warnings) = translate("newmtl m\nKe 2 4 0\nillum 2\n");
+        match m {
+            Material::Emissive(m) => {
+                assert!((m.ls - 4.0).abs() < 1e-9);
+                assert!((m.ce - Color::new(0.5, 1.0, 0.0)).norm() < 1e-9);
+            }
+            _ => panic!("expected an emissive material"),
+        }
+        assert!(warnings.is_empty());
+        let (m, warnings) = translate("newmtl m\nKa 10 10 10\nKd 1 1 1\n");
+        assert!(matches!(m, Material::Emissive(_)));
+        assert_eq!(warnings.len(), 1);
+        let (m, warnings) = translate("newmtl m\nKe bright\nillum 1\n");
+        assert!(matches!(m, Material::Matte(_)));
+        assert_eq!(warnings, ["material `m`: invalid Ke `bright`"]);
+    }
+
+    #[test]
+    fn textures_and_unknown_fields_are_reported() {
+        let (_, warnings) = translate("newmtl m\nmap_Kd wood.png\nPr 0.5\nillum 1\n");
+        assert_eq!(
+            warnings,
+            [
+                "material `m`: map_Kd `wood.png` ignored, textures are not supported",
+                "material `m`: unsupported field Pr ignored",
+            ]
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use std::fs;
+use std::str::SplitWhitespace;
+
+use nalgebra::{Point2, Point3};
+
+use crate::color::Color;
+use crate::error::SceneError;
+use crate::model::Vec3;
+
+// http://paulbourke.net/dataformats/ply/
+pub struct PlyMesh {
+    pub positions: Vec<Point3<f64>>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Point2<f64>>>,
+    pub colors: Option<Vec<Color>>,
+    pub faces: Vec<[u32; 3]>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::Char),
+            "uchar" | "uint8" => Some(Scalar::UChar),
+            "short" | "int16" => Some(Scalar::Short),
+            "ushort" | "uint16" => Some(Scalar::UShort),
+            "int" | "int32" => Some(Scalar::Int),
+            "uint" | "uint32" => Some(Scalar::UInt),
+            "float" | "float32" => Some(Scalar::Float),
+            "double" | "float64" => Some(Scalar::Double),
+            _ => None,
+        }
+    }
+
+    // colors stored as integers are in [0, 255]
+    fn is_integer(self) -> bool {
+        !matches!(self, Scalar::Float | Scalar::Double)
+    }
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name,
+        }
+    }
+}
+
+trait ValueReader {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String>;
+    fn line(&self) -> Option<usize>;
+}
+
+struct AsciiReader<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    words: SplitWhitespace<'a>,
+    line: usize,
+}
+
+impl<'a> ValueReader for AsciiReader<'a> {
+    fn read(&mut self, _scalar: Scalar) -> Result<f64, String> {
+        loop {
+            if let Some(word) = self.words.next() {
+                return word
+                    .parse()
+                    .map_err(|_| format!("invalid number `{}`", word));
+            }
+            let (i, line) = self
+                .lines
+                .next()
+                .ok_or_else(|| "unexpected end of file".to_owned())?;
+            self.line = i;
+            self.words = line.split_whitespace();
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        Some(self.line)
+    }
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut bytes: [u8; N] = self
+            .data
+            .get(self.pos..self.pos + N)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| "unexpected end of file".to_owned())?;
+        self.pos += N;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+}
+
+impl<'a> ValueReader for BinaryReader<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        Ok(match scalar {
+            Scalar::Char => i8::from_le_bytes(self.bytes()?) as f64,
+            Scalar::UChar => u8::from_le_bytes(self.bytes()?) as f64,
+            Scalar::Short => i16::from_le_bytes(self.bytes()?) as f64,
+            Scalar::UShort => u16::from_le_bytes(self.bytes()?) as f64,
+            Scalar::Int => i32::from_le_bytes(self.bytes()?) as f64,
+            Scalar::UInt => u32::from_le_bytes(self.bytes()?) as f64,
+            Scalar::Float => f32::from_le_bytes(self.bytes()?) as f64,
+            Scalar::Double => f64::from_le_bytes(self.bytes()?),
+        })
+    }
+
+    fn line(&self) -> Option<usize> {
+        None
+    }
+}
+
+pub fn load_ply(file_name: &str) -> Result<PlyMesh, SceneError> {
+    let data = fs::read(file_name).map_err(|_| SceneError::MissingFile {
+        file: file_name.to_owned(),
+    })?;
+    let parse_error = |line: Option<usize>, message: String| SceneError::Parse {
+        file: file_name.to_owned(),
+        line: line.map(|i| i + 1),
+        message,
+    };
+
+    let header_end = find(&data, b"end_header")
+        .ok_or_else(|| parse_error(None, "missing end_header".to_owned()))?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(data.len(), |i| header_end + i + 1);
+    let header = String::from_utf8_lossy(&data[..header_end]);
+    let (format, elements) = parse_header(&header).map_err(|(i, e)| parse_error(Some(i), e))?;
+    let body = &data[body_start..];
+
+    let header_lines = header.lines().count() + 1;
+    let mesh = if format == Format::Ascii {
+        let body = String::from_utf8_lossy(body);
+        let mut reader = AsciiReader {
+            lines: body.lines().enumerate(),
+            words: "".split_whitespace(),
+            line: 0,
+        };
+        read_body(&elements, &mut reader)
+            .map_err(|e| parse_error(reader.line().map(|i| i + header_lines), e))?
+    } else {
+        let mut reader = BinaryReader {
+            data: body,
+            pos: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        };
+        read_body(&elements, &mut reader).map_err(|e| parse_error(None, e))?
+    };
+
+    let vertex_count = mesh.positions.len();
+    if let Some(face) = mesh
+        .faces
+        .iter()
+        .flatten()
+        .find(|i| **i as usize >= vertex_count)
+    {
+        return Err(parse_error(
+            None,
+            format!("face references vertex {} of {}", face, vertex_count),
+        ));
+    }
+    Ok(mesh)
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), (usize, String)> {
+    let mut lines = header.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.trim() == "ply" => {}
+        _ => return Err((0, "not a ply file".to_owned())),
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for (i, line) in lines {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words[..] {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_owned(),
+                count: count
+                    .parse()
+                    .map_err(|_| (i, format!("invalid element count `{}`", count)))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => {
+                let property = match (Scalar::parse(count), Scalar::parse(item)) {
+                    (Some(count), Some(item)) => Property::List(name.to_owned(), count, item),
+                    _ => return Err((i, format!("invalid list property `{}`", name))),
+                };
+                push_property(&mut elements, property).map_err(|e| (i, e))?;
+            }
+            ["property", scalar, name] => {
+                let scalar = Scalar::parse(scalar)
+                    .ok_or_else(|| (i, format!("invalid property type `{}`", scalar)))?;
+                push_property(&mut elements, Property::Scalar(name.to_owned(), scalar))
+                    .map_err(|e| (i, e))?;
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err((i, format!("invalid header line `{}`", line))),
+        }
+    }
+    let format = format.ok_or_else(|| (0, "missing format".to_owned()))?;
+    Ok((format, elements))
+}
+
+fn push_property(elements: &mut [Element], property: Property) -> Result<(), String> {
+    elements
+        .last_mut()
+        .map(|element| element.properties.push(property))
+        .ok_or_else(|| "property before any element".to_owned())
+}
+
+fn read_body(elements: &[Element], reader: &mut dyn ValueReader) -> Result<PlyMesh, String> {
+    let mut mesh = PlyMesh {
+        positions: vec![],
+        normals: None,
+        uvs: None,
+        colors: None,
+        faces: vec![],
+    };
+    for element in elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, reader, &mut mesh)?,
+            "face" => read_faces(element, reader, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    read_properties(element, reader)?;
+                }
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+// reads one element, scalar properties yield a single value
+fn read_properties(
+    element: &Element,
+    reader: &mut dyn ValueReader,
+) -> Result<Vec<Vec<f64>>, String> {
+    element
+        .properties
+        .iter()
+        .map(|property| match property {
+            Property::Scalar(_, scalar) => Ok(vec![reader.read(*scalar)?]),
+            Property::List(_, count, item) => {
+                let count = reader.read(*count)? as usize;
+                (0..count).map(|_| reader.read(*item)).collect()
+            }
+        })
+        .collect()
+}
+
+fn read_vertices(
+    element: &Element,
+    reader: &mut dyn ValueReader,
+    mesh: &mut PlyMesh,
+) -> Result<(), String> {
+    let index = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .position(|p| names.contains(&p.name()))
+    };
+    let indices = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+        Some([index(names[0])?, index(names[1])?, index(names[2])?])
+    };
+    let position =
+        indices([&["x"], &["y"], &["z"]]).ok_or_else(|| "vertex without x, y and z".to_owned())?;
+    let normal = indices([&["nx"], &["ny"], &["nz"]]);
+    let uv = index(&["u", "s", "texture_u", "texture_s"]).zip(index(&[
+        "v",
+        "t",
+        "texture_v",
+        "texture_t",
+    ]));
+    let color = indices([&["red"], &["green"], &["blue"]]);
+    let color_scale = match color.map(|c| &element.properties[c[0]]) {
+        Some(Property::Scalar(_, scalar)) if scalar.is_integer() => 255.0,
+        _ => 1.0,
+    };
+
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut colors = vec![];
+    for _ in 0..element.count {
+        let values = read_properties(element, reader)?;
+        let value = |i: usize| values[i].first().copied().unwrap_or_default();
+        mesh.positions.push(Point3::new(
+            value(position[0]),
+            value(position[1]),
+            value(position[2]),
+        ));
+        if let Some(n) = normal {
+            normals.push(Vec3::new(value(n[0]), value(n[1]), value(n[2])));
+        }
+        if let Some((u, v)) = uv {
+            uvs.push(Point2::new(value(u), value(v)));
+        }
+        if let Some(c) = color {
+            colors.push(Color::new(value(c[0]), value(c[1]), value(c[2])) / color_scale);
+        }
+    }
+    mesh.normals = normal.map(|_| normals);
+    mesh.uvs = uv.map(|_| uvs);
+    mesh.colors = color.map(|_| colors);
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    reader: &mut dyn ValueReader,
+    mesh: &mut PlyMesh,
+) -> Result<(), String> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|p| matches!(p.name(), "vertex_indices" | "vertex_index"))
+        .ok_or_else(|| "face without vertex_indices".to_owned())?;
+    for _ in 0..element.count {
+        let values = read_properties(element, reader)?;
+        let polygon = values[indices]
+            .iter()
+            .map(|i| {
+                if *i >= 0.0 && i.fract() == 0.0 && *i <= f64::from(u32::MAX) {
+                    Ok(*i as u32)
+                } else {
+                    Err(format!("invalid vertex index {}", i))
+                }
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+        if polygon.len() < 3 {
+            return Err(format!("face with {} vertices", polygon.len()));
+        }
+        // triangulate polygons as a fan
+        for i in 1..polygon.len() - 1 {
+            mesh.faces.push([polygon[0], polygon[i], polygon[i + 1]]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::{empty_asset, fixture};
+    use crate::asset::white_matte;
+
+    // a unit square as one quad, header lines after the format line
+    fn square(format: &str, header: &str, body: &[u8]) -> Vec<u8> {
+        let mut data = format!("ply\nformat {} 1.0\n{}end_header\n", format, header).into_bytes();
+        data.extend(body);
+        data
+    }
+
+    const BINARY_HEADER: &str = "element vertex 4\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\n";
+
+    fn binary_body(to_bytes: fn(f32) -> [u8; 4], index_bytes: fn(i32) -> [u8; 4]) -> Vec<u8> {
+        let mut body = vec![];
+        for p in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ] {
+            for x in p {
+                body.extend(to_bytes(x));
+            }
+        }
+        body.push(4);
+        for i in 0..4 {
+            body.extend(index_bytes(i));
+        }
+        body
+    }
+
+    fn assert_square(mesh: &PlyMesh) {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[2], Point3::new(1.0, 1.0, 0.0));
+        // the quad is split as a fan
+        assert_eq!(mesh.faces, [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn ascii_is_parsed_with_vertex_attributes() {
+        let header = "comment a square\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\nproperty float s\nproperty float t\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\n";
+        let body = b"0 0 0 0 0 1 0 0 255 0 0
+1 0 0 0 0 1 1 0 255 0 0
+1 1 0 0 0 1 1 1 0 255 0
+0 1 0 0 0 1 0 1 0 0 51
+4 0 1 2 3
+";
+        let file = fixture("square.ply", &square("ascii", header, body));
+        let mesh = load_ply(&file).unwrap();
+        assert_square(&mesh);
+        assert!(mesh.normals.unwrap().iter().all(|n| *n == Vec3::z()));
+        assert_eq!(mesh.uvs.unwrap()[2], Point2::new(1.0, 1.0));
+        // integer colors are in [0, 255]
+        let colors = mesh.colors.unwrap();
+        assert_eq!(colors[0], Color::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[3], Color::new(0.0, 0.0, 0.2));
+    }
+
+    #[test]
+    fn binary_is_parsed_in_either_byte_order() {
+        let little = binary_body(f32::to_le_bytes, i32::to_le_bytes);
+        let file = fixture(
+            "little.ply",
+            &square("binary_little_endian", BINARY_HEADER, &little),
+        );
+        assert_square(&load_ply(&file).unwrap());
+        let big = binary_body(f32::to_be_bytes, i32::to_be_bytes);
+        let file = fixture("big.ply", &square("binary_big_endian", BINARY_HEADER, &big));
+        assert_square(&load_ply(&file).unwrap());
+    }
+
+    #[test]
+    fn truncated_binary_is_rejected() {
+        let mut body = binary_body(f32::to_le_bytes, i32::to_le_bytes);
+        body.truncate(body.len() - 2);
+        let file = fixture(
+            "truncated.ply",
+            &square("binary_little_endian", BINARY_HEADER, &body),
+        );
+        match load_ply(&file) {
+            Err(SceneError::Parse { message, .. }) => assert_eq!(message, "unexpected end of file"),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn ascii_error_names_the_line() {
+        let body = b"0 0 0\n1 0 0\n1 one 0\n0 1 0\n4 0 1 2 3\n";
+        let file = fixture("bad-number.ply", &square("ascii", BINARY_HEADER, body));
+        match load_ply(&file) {
+            Err(SceneError::Parse { line, message, .. }) => {
+                // 9 header lines, then the third vertex
+                assert_eq!(line, Some(12));
+                assert_eq!(message, "invalid number `one`");
+            }
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn face_out_of_range_is_rejected() {
+        let body = b"0 0 0\n1 0 0\n1 1 0\n0 1 0\n3 0 1 4\n";
+        let file = fixture("out-of-range.ply", &square("ascii", BINARY_HEADER, body));
+        assert!(matches!(load_ply(&file), Err(SceneError::Parse { .. })));
+    }
+
+    #[test]
+    fn ply_without_faces_is_skipped() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n";
+        let point_cloud = format!("{}end_header\n0 0 0\n1 0 0\n0 1 0\n", header);
+        let no_faces = format!(
+            "{}element face 0\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n",
+            header
+        );
+        for (name, content) in [("points.ply", point_cloud), ("no-faces.ply", no_faces)] {
+            let file = fixture(name, content.as_bytes());
+            let mut asset = empty_asset();
+            asset.add_ply(&file, white_matte(), 555.0).unwrap();
+            assert!(asset.meshes.is_empty());
+            assert_eq!(asset.warnings.len(), 1);
+        }
+    }
+
+    #[test]
+    fn ply_with_invalid_index_is_rejected() {
+        for (name, index) in [("negative.ply", "-1"), ("fraction.ply", "1.5")] {
+            let content = format!(
+                "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar float vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 {} 2\n",
+                index
+            );
+            let file = fixture(name, content.as_bytes());
+            assert!(matches!(load_ply(&file), Err(SceneError::Parse { .. })));
+        }
+    }
+}
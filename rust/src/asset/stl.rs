@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::fs;
+
+use nalgebra::Point3;
+
+use crate::error::SceneError;
+use crate::model::Vec3;
+
+// faces index into positions, unwelded meshes have three positions per face
+pub struct StlMesh {
+    pub positions: Vec<Point3<f64>>,
+    pub normals: Option<Vec<Vec3>>,
+    pub faces: Vec<[usize; 3]>,
+}
+
+pub fn load_stl(file_name: &str) -> Result<StlMesh, SceneError> {
+    let data = fs::read(file_name).map_err(|_| SceneError::MissingFile {
+        file: file_name.to_owned(),
+    })?;
+    // binary files may also start with "solid", trust the size instead
+    let positions = if binary_size(&data) == Some(data.len()) || !data.starts_with(b"solid") {
+        read_binary(&data).map_err(|message| SceneError::Parse {
+            file: file_name.to_owned(),
+            line: None,
+            message,
+        })?
+    } else {
+        read_ascii(&String::from_utf8_lossy(&data)).map_err(|(line, message)| {
+            SceneError::Parse {
+                file: file_name.to_owned(),
+                line: Some(line),
+                message,
+            }
+        })?
+    };
+    let faces = (0..positions.len() / 3)
+        .map(|f| [3 * f, 3 * f + 1, 3 * f + 2])
+        .collect();
+    Ok(StlMesh {
+        positions,
+        normals: None,
+        faces,
+    })
+}
+
+// 80 byte header, triangle count, then normal, three vertices and attributes per triangle
+fn read_binary(data: &[u8]) -> Result<Vec<Point3<f64>>, String> {
+    let float = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .ok_or_else(|| "unexpected end of file".to_owned())
+    };
+    let count = triangle_count(data).ok_or_else(|| "missing triangle count".to_owned())?;
+    if binary_size(data)
+        .filter(|size| *size <= data.len())
+        .is_none()
+    {
+        return Err(format!(
+            "{} triangles do not fit in {} bytes",
+            count,
+            data.len()
+        ));
+    }
+    let mut positions = Vec::with_capacity(3 * count);
+    for t in 0..count {
+        let start = 84 + 50 * t + 12;
+        for v in 0..3 {
+            let offset = start + 12 * v;
+            positions.push(Point3::new(
+                float(offset)?,
+                float(offset + 4)?,
+                float(offset + 8)?,
+            ));
+        }
+    }
+    Ok(positions)
+}
+
+fn triangle_count(data: &[u8]) -> Option<usize> {
+    data.get(80..84)
+        .map(|n| u32::from_le_bytes([n[0], n[1], n[2], n[3]]) as usize)
+}
+
+// size of a binary file with the triangle count it gives, if that fits in memory
+fn binary_size(data: &[u8]) -> Option<usize> {
+    triangle_count(data)?.checked_mul(50)?.checked_add(84)
+}
+
+fn read_ascii(content: &str) -> Result<Vec<Point3<f64>>, (usize, String)> {
+    let mut positions = vec![];
+    let mut facet = 0;
+    for (i, line) in content.lines().enumerate() {
+        let line_number = i + 1;
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words[..] {
+            ["vertex", x, y, z] => {
+                let parse = |w: &str| {
+                    w.parse::<f64>()
+                        .map_err(|_| (line_number, format!("invalid number `{}`", w)))
+                };
+                positions.push(Point3::new(parse(x)?, parse(y)?, parse(z)?));
+                facet += 1;
+            }
+            ["vertex", ..] => {
+                return Err((line_number, "vertex needs three coordinates".to_owned()))
+            }
+            ["endfacet"] => {
+                if facet != 3 {
+                    return Err((line_number, format!("facet with {} vertices", facet)));
+                }
+                facet = 0;
+            }
+            _ => {}
+        }
+    }
+    Ok(positions)
+}
+
+// Merges each vertex into the first one kept within the tolerance, faces
+// collapsing onto an edge or a point are removed. Kept vertices are binned in
+// cells as wide as the tolerance, so only the neighbouring cells are searched
+pub fn weld(mesh: &StlMesh, tolerance: f64) -> Result<StlMesh, String> {
+    if !(tolerance > 0.0 && tolerance.is_finite()) {
+        return Err(format!("invalid weld tolerance {}", tolerance));
+    }
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut positions: Vec<Point3<f64>> = vec![];
+    let remap: Vec<usize> = mesh
+        .positions
+        .iter()
+        .map(|p| {
+            let cell = [0, 1, 2].map(|i| (p[i] / tolerance).floor() as i64);
+            let nearby = (0..27)
+                .map(|n| [n % 3, n / 3 % 3, n / 9].map(|d| d - 1))
+                .flat_map(|d| cells.get(&[0, 1, 2].map(|i| cell[i] + d[i])))
+                .flatten()
+                .copied()
+                .find(|v| (positions[*v] - p).norm() <= tolerance);
+            nearby.unwrap_or_else(|| {
+                positions.push(*p);
+                cells.entry(cell).or_default().push(positions.len() - 1);
+                positions.len() - 1
+            })
+        })
+        .collect();
+    let faces = mesh
+        .faces
+        .iter()
+        .map(|face| face.map(|i| remap[i]))
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .collect();
+    Ok(StlMesh {
+        positions,
+        normals: None,
+        faces,
+    })
+}
+
+// Vertex normals averaging the faces around each vertex, faces bent by more
+// than the crease angle keep their hard edge by splitting the vertex
+pub fn smooth_normals(mesh: &StlMesh, crease_angle: f64) -> StlMesh {
+    let face_normals: Vec<Vec3> = mesh
+        .faces
+        .iter()
+        .map(|[a, b, c]| {
+            let (a, b, c) = (mesh.positions[*a], mesh.positions[*b], mesh.positions[*c]);
+            (b - a).cross(&(c - a))
+        })
+        .collect();
+    let mut vertex_faces: Vec<Vec<usize>> = vec![vec![]; mesh.positions.len()];
+    for (f, face) in mesh.faces.iter().enumerate() {
+        for v in face {
+            vertex_faces[*v].push(f);
+        }
+    }
+    let cos_crease = crease_angle.cos();
+    // corners sharing a vertex and the same set of smoothed faces share a normal
+    let mut split: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let faces = mesh
+        .faces
+        .iter()
+        .enumerate()
+        .map(|(f, face)| {
+            let normal = face_normals[f].normalize();
+            face.map(|v| {
+                let smoothed: Vec3 = vertex_faces[v]
+                    .iter()
+                    .map(|g| face_normals[*g])
+                    .filter(|n| n.normalize().dot(&normal) >= cos_crease)
+                    .sum::<Vec3>()
+                    .normalize();
+                let key = (v, [smoothed.x, smoothed.y, smoothed.z].map(f64::to_bits));
+                *split.entry(key).or_insert_with(|| {
+                    positions.push(mesh.positions[v]);
+                    normals.push(smoothed);
+                    positions.len() - 1
+                })
+            })
+        })
+        .collect();
+    StlMesh {
+        positions,
+        normals: Some(normals),
+        faces,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::tests::{empty_asset, fixture};
+    use crate::asset::white_matte;
+
+    fn stl_binary(triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut data = vec![0; 80];
+        data.extend((triangles.len() as u32).to_le_bytes());
+        for t in triangles {
+            data.extend([0; 12]);
+            for v in t {
+                for x in v {
+                    data.extend(x.to_le_bytes());
+                }
+            }
+            data.extend([0; 2]);
+        }
+        data
+    }
+
+    fn ascii(facets: &str) -> String {
+        format!("solid part\n{}endsolid part\n", facets)
+    }
+
+    const FACET: &str = "facet normal 0 0 1
+outer loop
+vertex 0 0 0
+vertex 1 0 0
+vertex 0 1 0
+endloop
+endfacet
+";
+
+    #[test]
+    fn binary_and_ascii_are_read() {
+        let triangle = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        // binary headers may start with "solid" too
+        let mut data = stl_binary(&[triangle, triangle]);
+        data[..5].copy_from_slice(b"solid");
+        let file = fixture("binary-solid.stl", &data);
+        let mesh = load_stl(&file).unwrap();
+        assert_eq!(mesh.faces, [[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(mesh.positions[4], Point3::new(1.0, 0.0, 0.0));
+
+        let file = fixture("ascii.stl", ascii(FACET).as_bytes());
+        let mesh = load_stl(&file).unwrap();
+        assert_eq!(mesh.faces, [[0, 1, 2]]);
+        assert_eq!(mesh.positions[2], Point3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ascii_error_names_the_line() {
+        let facet = FACET.replace("vertex 1 0 0", "vertex 1 0");
+        let file = fixture("short-vertex.stl", ascii(&facet).as_bytes());
+        match load_stl(&file) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, Some(5)),
+            other => panic!("{:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn stl_without_triangles_is_skipped() {
+        let file = fixture("no-triangles.stl", &stl_binary(&[]));
+        let mut asset = empty_asset();
+        asset.add_stl(&file, white_matte(), 555.0, None).unwrap();
+        assert!(asset.meshes.is_empty());
+        assert_eq!(asset.warnings.len(), 1);
+    }
+
+    #[test]
+    fn stl_with_too_many_triangles_is_rejected() {
+        let mut data = stl_binary(&[]);
+        data[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        let file = fixture("truncated.stl", &data);
+        assert!(matches!(load_stl(&file), Err(SceneError::Parse { .. })));
+    }
+
+    #[test]
+    fn stl_welded_to_nothing_is_skipped() {
+        let small = [[0.0, 0.0, 0.0], [0.1, 0.0, 0.0], [0.0, 0.1, 0.0]];
+        let file = fixture("collapsed.stl", &stl_binary(&[small, small]));
+        let mut asset = empty_asset();
+        asset
+            .add_stl(&file, white_matte(), 555.0, Some(10.0))
+            .unwrap();
+        assert!(asset.meshes.is_empty());
+        assert_eq!(asset.warnings.len(), 1);
+    }
+
+    #[test]
+    fn weld_merges_across_cells() {
+        // either side of a cell boundary, and a face too wide to collapse
+        let mesh = StlMesh {
+            positions: vec![
+                Point3::new(0.999, 0.0, 0.0),
+                Point3::new(1.001, 0.0, 0.0),
+                Point3::new(5.0, 0.0, 0.0),
+                Point3::new(0.0, 5.0, 0.0),
+            ],
+            normals: None,
+            faces: vec![[0, 2, 3], [1, 2, 3]],
+        };
+        let welded = weld(&mesh, 0.01).unwrap();
+        assert_eq!(welded.positions.len(), 3);
+        assert_eq!(welded.faces[0], welded.faces[1]);
+        for tolerance in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(weld(&mesh, tolerance).is_err());
+        }
+    }
+
+    #[test]
+    fn crease_keeps_hard_edges() {
+        // two faces sharing the edge from (0, 0, 0) to (0, 1, 0), folded by 90 degrees
+        let mesh = StlMesh {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+            ],
+            normals: None,
+            faces: vec![[0, 2, 1], [0, 1, 3]],
+        };
+        let hard = smooth_normals(&mesh, 30_f64.to_radians());
+        assert_eq!(hard.positions.len(), 6);
+        let normals = hard.normals.unwrap();
+        assert!((normals[hard.faces[0][0]] - Vec3::z()).norm() < 1e-12);
+        assert!((normals[hard.faces[1][0]] - Vec3::x()).norm() < 1e-12);
+
+        let smooth = smooth_normals(&mesh, 120_f64.to_radians());
+        assert_eq!(smooth.positions.len(), 4);
+        let shared = smooth.normals.unwrap()[smooth.faces[0][0]];
+        assert!((shared - Vec3::new(1.0, 0.0, 1.0).normalize()).norm() < 1e-12);
+    }
+}
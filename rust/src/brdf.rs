@@ -1,6 +1,7 @@
-use std::f64::consts::FRAC_1_PI;
+use std::f64::consts::{FRAC_1_PI, PI};
 
 use crate::color::Color;
+use crate::geometric_object::plane_axes;
 use crate::model::Vec3;
 use crate::ray::RayHit;
 
@@ -31,6 +32,18 @@ impl Lambertian {
     pub fn new(kd: f64, cd: Color) -> Lambertian {
         Lambertian { kd, cd }
     }
+
+    // cosine weighted direction about the normal for (u, v) in the unit square
+    pub fn sample_wi(&self, hit: &RayHit, u: f64, v: f64) -> Vec3 {
+        let (a, b) = plane_axes(&hit.normal);
+        let r = u.sqrt();
+        let phi = 2.0 * PI * v;
+        (a * phi.cos() + b * phi.sin()) * r + hit.normal * (1.0 - u).max(0.0).sqrt()
+    }
+
+    pub fn pdf(&self, hit: &RayHit, wi: &Vec3) -> f64 {
+        hit.normal.dot(wi).max(0.0) * FRAC_1_PI
+    }
 }
 
 impl PerfectSpecular {
@@ -43,11 +56,35 @@ impl GlossySpecular {
     pub fn new(ks: f64, exp: f64) -> GlossySpecular {
         GlossySpecular { ks, exp }
     }
+
+    // direction about the mirror of wo with density proportional to cos^exp
+    // of the angle between them, for (u, v) in the unit square
+    pub fn sample_wi(&self, hit: &RayHit, wo: &Vec3, u: f64, v: f64) -> Vec3 {
+        let r = hit.normal * (2.0 * hit.normal.dot(wo)) - wo;
+        let (a, b) = plane_axes(&r);
+        let cos_alpha = u.powf((self.exp + 1.0).recip());
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        (a * phi.cos() + b * phi.sin()) * sin_alpha + r * cos_alpha
+    }
+
+    pub fn pdf(&self, hit: &RayHit, wo: &Vec3, wi: &Vec3) -> f64 {
+        let r = hit.normal * (2.0 * hit.normal.dot(wo)) - wo;
+        let rdotwi = r.dot(wi);
+        if rdotwi <= 0.0 {
+            return 0.0;
+        }
+        (self.exp + 1.0) / (2.0 * PI) * rdotwi.powf(self.exp)
+    }
 }
 
 impl BRDF for Lambertian {
-    fn f(&self, _hit: &RayHit, _wo: &Vec3, _wi: &Vec3) -> Color {
-        self.rho() * FRAC_1_PI
+    fn f(&self, hit: &RayHit, _wo: &Vec3, _wi: &Vec3) -> Color {
+        // per-vertex colors tint the diffuse color
+        let rho = hit
+            .vertex_color
+            .map_or_else(|| self.rho(), |c| self.rho().component_mul(&c));
+        rho * FRAC_1_PI
     }
 
     fn rho(&self) -> Color {
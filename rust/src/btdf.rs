@@ -0,0 +1,49 @@
+use crate::color::Color;
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+pub trait BTDF {
+    // transmitted direction and the transmission coefficient along it
+    fn sample_f(&self, hit: &RayHit, wo: &Vec3) -> (Vec3, Color);
+    // total internal reflection
+    fn tir(&self, hit: &RayHit) -> bool;
+}
+
+pub struct PerfectTransmitter {
+    pub kt: f64,  // transmission coefficient [0, 1]
+    pub ior: f64, // index of refraction
+}
+
+impl PerfectTransmitter {
+    pub fn new(kt: f64, ior: f64) -> PerfectTransmitter {
+        PerfectTransmitter { kt, ior }
+    }
+
+    // relative index of refraction, inverted when the ray leaves the medium
+    fn eta(&self, hit: &RayHit) -> f64 {
+        if hit.inside {
+            self.ior.recip()
+        } else {
+            self.ior
+        }
+    }
+}
+
+impl BTDF for PerfectTransmitter {
+    fn sample_f(&self, hit: &RayHit, wo: &Vec3) -> (Vec3, Color) {
+        let normal = hit.normal;
+        let eta = self.eta(hit);
+        let cos_thetai = normal.dot(wo);
+        let cos_theta2 = (1.0 - (1.0 - cos_thetai * cos_thetai) / (eta * eta)).sqrt();
+        let wt = -wo / eta - normal * (cos_theta2 - cos_thetai / eta);
+        let ft = Color::repeat(self.kt / (eta * eta)) / normal.dot(&wt).abs();
+        (wt, ft)
+    }
+
+    fn tir(&self, hit: &RayHit) -> bool {
+        let wo = -hit.ray.dir.normalize();
+        let cos_thetai = hit.normal.dot(&wo);
+        let eta = self.eta(hit);
+        1.0 - (1.0 - cos_thetai * cos_thetai) / (eta * eta) < 0.0
+    }
+}
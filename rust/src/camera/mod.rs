@@ -31,7 +31,15 @@ pub struct CameraSetting {
 
 impl CameraSetting {
     pub fn new(eye: Point3<f64>, lookat: Point3<f64>, view_plane_distance: f64) -> CameraSetting {
-        let up = Vec3::new(0.0, 1.0, 0.0);
+        CameraSetting::with_up(eye, lookat, Vec3::new(0.0, 1.0, 0.0), view_plane_distance)
+    }
+
+    pub fn with_up(
+        eye: Point3<f64>,
+        lookat: Point3<f64>,
+        up: Vec3,
+        view_plane_distance: f64,
+    ) -> CameraSetting {
         let w = (eye - lookat).normalize();
         let u = up.cross(&w).normalize();
         let v = w.cross(&u).normalize();
@@ -10,6 +10,11 @@ pub fn to_rgb(color: &Color) -> Vec<u8> {
         .collect()
 }
 
+// relative luminance of linear sRGB
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
 fn tone_mapping(color: &Color) -> Color {
     let max = color.x.max(color.y).max(color.z).max(1.0);
     color / max
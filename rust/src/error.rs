@@ -0,0 +1,135 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SceneError {
+    MissingFile {
+        file: String,
+    },
+    Parse {
+        file: String,
+        line: Option<usize>,
+        message: String,
+    },
+    MissingMtl {
+        file: String,
+        line: Option<usize>,
+        mtl: String,
+    },
+    UnknownMaterial {
+        file: Option<String>,
+        line: Option<usize>,
+        material: String,
+    },
+    DegenerateGeometry {
+        file: Option<String>,
+        message: String,
+    },
+}
+
+impl SceneError {
+    // names the file for errors raised by code that doesn't know it
+    pub fn with_file(self, file_name: &str) -> SceneError {
+        match self {
+            SceneError::UnknownMaterial {
+                file: None,
+                line,
+                material,
+            } => SceneError::UnknownMaterial {
+                file: Some(file_name.to_owned()),
+                line,
+                material,
+            },
+            SceneError::DegenerateGeometry {
+                file: None,
+                message,
+            } => SceneError::DegenerateGeometry {
+                file: Some(file_name.to_owned()),
+                message,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::MissingFile { file } => write!(f, "{}: file not found", file),
+            SceneError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}{}: {}", file, at_line(*line), message),
+            SceneError::MissingMtl { file, line, mtl } => {
+                write!(
+                    f,
+                    "{}{}: material library {} not found",
+                    file,
+                    at_line(*line),
+                    mtl
+                )
+            }
+            SceneError::UnknownMaterial {
+                file,
+                line,
+                material,
+            } => write!(
+                f,
+                "{}{}: unknown material `{}`",
+                file.as_deref().unwrap_or("scene"),
+                at_line(*line),
+                material
+            ),
+            SceneError::DegenerateGeometry { file, message } => write!(
+                f,
+                "{}: degenerate geometry, {}",
+                file.as_deref().unwrap_or("scene"),
+                message
+            ),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+fn at_line(line: Option<usize>) -> String {
+    line.map_or_else(String::new, |line| format!(":{}", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_file_only_names_errors_without_one() {
+        let error = SceneError::DegenerateGeometry {
+            file: None,
+            message: "a CSG operand is an open surface".to_owned(),
+        };
+        assert_eq!(
+            error.with_file("scene.obj").to_string(),
+            "scene.obj: degenerate geometry, a CSG operand is an open surface"
+        );
+        let error = SceneError::UnknownMaterial {
+            file: Some("a.obj".to_owned()),
+            line: Some(4),
+            material: "red".to_owned(),
+        };
+        assert_eq!(
+            error.with_file("b.obj").to_string(),
+            "a.obj:4: unknown material `red`"
+        );
+    }
+
+    #[test]
+    fn line_is_shown_when_known() {
+        let error = |line| SceneError::Parse {
+            file: "mesh.ply".to_owned(),
+            line,
+            message: "bad index".to_owned(),
+        };
+        assert_eq!(error(Some(12)).to_string(), "mesh.ply:12: bad index");
+        assert_eq!(error(None).to_string(), "mesh.ply: bad index");
+    }
+}
@@ -7,24 +7,26 @@ use crate::geometric_object::Geometry;
 use crate::model::Vec3;
 use crate::ray::{HitRecord, Ray};
 
+// A node without children is empty and never hit
 pub struct BvhNode {
-    pub left: Arc<dyn Geometry + Send + Sync>,
-    pub right: Arc<dyn Geometry + Send + Sync>,
+    pub left: Option<Arc<dyn Geometry + Send + Sync>>,
+    pub right: Option<Arc<dyn Geometry + Send + Sync>>,
     pub aabb: AABB,
-    pub children: usize,
 }
 
 impl Geometry for BvhNode {
     fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let left = self.left.as_ref()?;
         if !self.aabb.intersects(ray, t_min, t_max) {
             return None;
         }
-        if self.children == 1 {
-            return self.left.intersects(ray, t_min, t_max);
-        }
-        self.left.intersects(ray, t_min, t_max).map_or_else(
-            || self.right.intersects(ray, t_min, t_max),
-            |r1| self.right.intersects(ray, t_min, r1.dist).or(Some(r1)),
+        let right = match &self.right {
+            Some(right) => right,
+            None => return left.intersects(ray, t_min, t_max),
+        };
+        left.intersects(ray, t_min, t_max).map_or_else(
+            || right.intersects(ray, t_min, t_max),
+            |r1| right.intersects(ray, t_min, r1.dist).or(Some(r1)),
         )
     }
 
@@ -57,46 +59,113 @@ impl Geometry for BvhNode {
     fn get_material_id(&self) -> usize {
         0
     }
+
+    fn material_ids(&self) -> Vec<usize> {
+        self.left
+            .iter()
+            .chain(&self.right)
+            .flat_map(|child| child.material_ids())
+            .collect()
+    }
 }
 
 impl BvhNode {
     pub fn new(objects: Vec<Arc<dyn Geometry + Send + Sync>>, start: usize, end: usize) -> BvhNode {
         let mut objects = objects;
+        let end = end.max(start);
+        BvhNode::build(&mut objects[start..end])
+    }
+
+    // sorts the slice in place, so the levels share the one Vec of objects
+    fn build(objects: &mut [Arc<dyn Geometry + Send + Sync>]) -> BvhNode {
         let axis = thread_rng().gen_range(0..3);
         let comparator = box_compare(axis);
 
-        let span = end - start;
-        if span == 1 {
-            BvhNode {
-                left: objects[start].clone(),
-                right: objects[start].clone(),
-                aabb: objects[start].get_bounding_box(),
-                children: 1,
-            }
-        } else {
-            objects[start..end].sort_by(comparator);
-            let mid = start + span / 2;
-            let left = Arc::new(BvhNode::new(objects.clone(), start, mid));
-            let right = Arc::new(BvhNode::new(objects, mid, end));
-            let box_left = left.get_bounding_box();
-            let box_right = right.get_bounding_box();
-            BvhNode {
-                left,
-                right,
-                aabb: AABB::get_surrounding_aabb(&box_left, &box_right),
-                children: 2,
+        match objects {
+            [] => BvhNode {
+                left: None,
+                right: None,
+                aabb: AABB::new(Point3::origin(), Point3::origin()),
+            },
+            [object] => BvhNode {
+                left: Some(object.clone()),
+                right: None,
+                aabb: object.get_bounding_box(),
+            },
+            _ => {
+                objects.sort_by(comparator);
+                let (left, right) = objects.split_at_mut(objects.len() / 2);
+                let left = Arc::new(BvhNode::build(left));
+                let right = Arc::new(BvhNode::build(right));
+                let box_left = left.get_bounding_box();
+                let box_right = right.get_bounding_box();
+                BvhNode {
+                    left: Some(left),
+                    right: Some(right),
+                    aabb: AABB::get_surrounding_aabb(&box_left, &box_right),
+                }
             }
         }
     }
 }
 
-fn box_compare<T: ?Sized>(axis: usize) -> Box<dyn Fn(&Arc<T>, &Arc<T>) -> std::cmp::Ordering>
+fn box_compare<T>(axis: usize) -> impl Fn(&Arc<T>, &Arc<T>) -> std::cmp::Ordering
 where
-    T: Geometry + Send + Sync,
+    T: Geometry + Send + Sync + ?Sized,
 {
-    Box::new(move |a, b| {
+    move |a, b| {
         let box_a = a.get_bounding_box();
         let box_b = b.get_bounding_box();
         box_a.min[axis].partial_cmp(&box_b.min[axis]).unwrap()
-    })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Matrix4;
+
+    use super::*;
+    use crate::geometric_object::{Instance, MeshBvh, TriangleMesh};
+
+    // instances of one shared square, at z = 1, 2, ... in normalized units
+    fn instances(count: usize) -> Vec<Arc<dyn Geometry + Send + Sync>> {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(2.0, 0.0, 1.0),
+            Point3::new(2.0, 2.0, 1.0),
+            Point3::new(0.0, 2.0, 1.0),
+        ];
+        let mesh = TriangleMesh::new(7, positions, vec![[0, 1, 2], [0, 2, 3]], 2.0);
+        let blas: Arc<dyn Geometry + Send + Sync> = Arc::new(MeshBvh::new(Arc::new(mesh)));
+        (1..=count)
+            .rev()
+            .map(|z| {
+                let translation = Matrix4::new_translation(&Vec3::new(0.0, 0.0, z as f64));
+                Arc::new(Instance::new(blas.clone(), translation, 2.0).unwrap())
+                    as Arc<dyn Geometry + Send + Sync>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nearest_instance_is_hit() {
+        let objects = instances(6);
+        let len = objects.len();
+        let tlas = BvhNode::new(objects, 0, len);
+        let ray = Ray::new(Point3::new(0.1, 0.2, -1.0), Vec3::z());
+        let hit = tlas.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 2.0).abs() < 1e-9);
+        assert_eq!(hit.material_id, 7);
+        let hit = tlas.intersects(&ray, 2.5, f64::INFINITY).unwrap();
+        assert!((hit.dist - 3.0).abs() < 1e-9);
+        assert_eq!(tlas.material_ids(), [7; 6]);
+    }
+
+    #[test]
+    fn empty_range_is_never_hit() {
+        let tlas = BvhNode::new(instances(2), 1, 1);
+        let ray = Ray::new(Point3::new(0.1, 0.2, -1.0), Vec3::z());
+        assert!(tlas.intersects(&ray, 0.0, f64::INFINITY).is_none());
+        assert!(tlas.material_ids().is_empty());
+    }
 }
@@ -0,0 +1,169 @@
+use nalgebra::{Point2, Point3};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{
+    cap_root, nearer, nearest_root, surface_distance, sweep_angle, Frame, Geometry,
+};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+use crate::solver::solve_quadratic;
+
+// Cone from a base of the given radius to its apex up the axis, the cap closes the base
+pub struct Cone {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material_id: usize,
+}
+
+impl Cone {
+    pub fn new(
+        material_id: usize,
+        base: Point3<f64>,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        scale: f64,
+    ) -> Cone {
+        let mut cone = Cone {
+            frame: Frame::new(base, axis),
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            material_id,
+        };
+        cone.scale(scale);
+        cone
+    }
+
+    // only the part swept from 0 to phi_max radians around the axis
+    pub fn with_sweep(mut self, phi_max: f64) -> Cone {
+        self.phi_max = phi_max;
+        self
+    }
+
+    pub fn with_caps(mut self) -> Cone {
+        self.capped = true;
+        self
+    }
+
+    fn in_sweep(&self, p: &Point3<f64>) -> bool {
+        sweep_angle(p) <= self.phi_max
+    }
+
+    // slope of the radius along the axis
+    fn k(&self) -> f64 {
+        self.radius / self.height
+    }
+
+    fn local_normal(&self, p: &Point3<f64>) -> Vec3 {
+        let k2 = self.k() * self.k();
+        Vec3::new(p.x, k2 * (self.height - p.y), p.z)
+    }
+
+    fn side_distance(&self, p: &Point3<f64>) -> f64 {
+        let k2 = self.k() * self.k();
+        let r2 = p.x * p.x + p.z * p.z;
+        let h2 = k2 * (self.height - p.y) * (self.height - p.y);
+        surface_distance(r2 - h2, r2 + h2, 2.0 * self.local_normal(p).norm(), 6)
+    }
+}
+
+impl Geometry for Cone {
+    // x^2 + z^2 = k^2 (h - y)^2
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.to_local(ray);
+        let (o, d) = (local.origin, local.dir);
+        let k2 = self.k() * self.k();
+        let h = self.height - o.y;
+        let roots = solve_quadratic([
+            o.x * o.x + o.z * o.z - k2 * h * h,
+            2.0 * (o.x * d.x + o.z * d.z + k2 * h * d.y),
+            d.x * d.x + d.z * d.z - k2 * d.y * d.y,
+        ]);
+        let side = nearest_root(&local, roots, t_min, t_max, |p| {
+            p.y >= 0.0 && p.y <= self.height && self.in_sweep(p)
+        })
+        .map(|(t, p)| {
+            let uv = Point2::new(sweep_angle(&p) / self.phi_max, p.y / self.height);
+            (t, self.local_normal(&p), uv, self.side_distance(&p))
+        });
+
+        let cap = if self.capped {
+            nearest_root(&local, cap_root(&local, 0.0), t_min, t_max, |p| {
+                p.x * p.x + p.z * p.z <= self.radius * self.radius && self.in_sweep(p)
+            })
+            .map(|(t, p)| {
+                let r = (p.x * p.x + p.z * p.z).sqrt();
+                let uv = Point2::new(sweep_angle(&p) / self.phi_max, r / self.radius);
+                (t, Vec3::new(0.0, -1.0, 0.0), uv, p.y.abs())
+            })
+        } else {
+            None
+        };
+
+        Some(
+            self.frame
+                .hit_record(ray, nearer(side, cap)?, self.material_id),
+        )
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.frame.scale(l);
+        self.radius = (self.radius * 2.0) / l;
+        self.height = (self.height * 2.0) / l;
+    }
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let p = self.frame.to_local_point(p);
+        let normal = if self.capped && p.y.abs() < self.side_distance(&p) {
+            Vec3::new(0.0, -1.0, 0.0)
+        } else {
+            self.local_normal(&p)
+        };
+        self.frame.to_world(&normal).normalize()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.frame
+            .to_world_point(&Point3::new(0.0, self.height / 2.0, 0.0))
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.get_bounding_box().min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.get_bounding_box().max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        self.frame.bounding_box(
+            Point3::new(-self.radius, 0.0, -self.radius),
+            Point3::new(self.radius, self.height, self.radius),
+        )
+    }
+
+    // the side only, its area grows linearly towards the base
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        self.frame.sample_surface(sample_points_sqrt, 1.0, |s| {
+            let phi = s.x * self.phi_max;
+            let r = self.radius * (1.0 - s.y);
+            let p = Point3::new(r * phi.cos(), s.y * self.height, r * phi.sin());
+            (p, 1.0 - s.y)
+        })
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    // partial sweeps are open along their cut, caps only close the ends
+    fn is_closed(&self) -> bool {
+        self.capped && self.phi_max >= 2.0 * PI
+    }
+}
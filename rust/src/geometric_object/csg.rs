@@ -0,0 +1,290 @@
+use nalgebra::{center, Point3};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::error::SceneError;
+use crate::geometric_object::{Crossings, Geometry};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+
+#[derive(Clone, Copy)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference, // left minus right
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// Combines two closed solids with outward normals, open surfaces are rejected.
+// The boundary keeps the material of the surface it comes from
+pub struct Csg {
+    operation: CsgOperation,
+    left: Arc<dyn Geometry + Send + Sync>,
+    right: Arc<dyn Geometry + Send + Sync>,
+    aabb: AABB,
+    reported: AtomicBool, // a ray couldn't be classified, warned once
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        left: Arc<dyn Geometry + Send + Sync>,
+        right: Arc<dyn Geometry + Send + Sync>,
+    ) -> Result<Csg, SceneError> {
+        if !(left.is_closed() && right.is_closed()) {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: "a CSG operand is an open surface".to_owned(),
+            });
+        }
+        let (a, b) = (left.get_bounding_box(), right.get_bounding_box());
+        let aabb = match operation {
+            CsgOperation::Union => AABB::get_surrounding_aabb(&a, &b),
+            CsgOperation::Intersection => AABB::new(a.min.sup(&b.min), a.max.inf(&b.max)),
+            CsgOperation::Difference => a,
+        };
+        Ok(Csg {
+            operation,
+            left,
+            right,
+            aabb,
+            reported: AtomicBool::new(false),
+        })
+    }
+
+    pub fn union(
+        left: Arc<dyn Geometry + Send + Sync>,
+        right: Arc<dyn Geometry + Send + Sync>,
+    ) -> Result<Csg, SceneError> {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(
+        left: Arc<dyn Geometry + Send + Sync>,
+        right: Arc<dyn Geometry + Send + Sync>,
+    ) -> Result<Csg, SceneError> {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(
+        left: Arc<dyn Geometry + Send + Sync>,
+        right: Arc<dyn Geometry + Send + Sync>,
+    ) -> Result<Csg, SceneError> {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+
+    // Walks the crossings of both solids in order up to t_max, keeping those
+    // where the ray goes in or out of the combined solid
+    fn walk(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        first_only: bool,
+    ) -> Result<Crossings, SceneError> {
+        if !self.aabb.intersects(ray, t_min, t_max) {
+            return Ok(Crossings {
+                starts_inside: false,
+                hits: vec![],
+            });
+        }
+        let left = self.left.crossings(ray, t_min, t_max)?;
+        let right = self.right.crossings(ray, t_min, t_max)?;
+        let mut in_left = left.starts_inside;
+        let mut in_right = right.starts_inside;
+        let starts_inside = self.operation.contains(in_left, in_right);
+
+        let mut hits: Vec<(bool, HitRecord)> = left
+            .hits
+            .into_iter()
+            .map(|hit| (true, hit))
+            .chain(right.hits.into_iter().map(|hit| (false, hit)))
+            .collect();
+        hits.sort_by(|a, b| a.1.dist.total_cmp(&b.1.dist));
+
+        let mut boundary = vec![];
+        for (from_left, mut hit) in hits {
+            let was_inside = self.operation.contains(in_left, in_right);
+            let entering = hit.normal.dot(&ray.dir) < 0.0;
+            if from_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            if was_inside != self.operation.contains(in_left, in_right) {
+                // the surface of the removed solid faces into it
+                if !from_left && matches!(self.operation, CsgOperation::Difference) {
+                    hit.normal = -hit.normal;
+                    hit.geometric_normal = -hit.geometric_normal;
+                }
+                boundary.push(hit);
+                if first_only {
+                    break;
+                }
+            }
+        }
+        Ok(Crossings {
+            starts_inside,
+            hits: boundary,
+        })
+    }
+}
+
+impl Geometry for Csg {
+    // only the nearest boundary is needed, rays whose crossings can't all be
+    // found can't be classified and miss
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        match self.walk(ray, t_min, t_max, true) {
+            Ok(crossings) => crossings.hits.into_iter().next(),
+            Err(error) => {
+                if !self.reported.swap(true, Ordering::Relaxed) {
+                    eprintln!("warning: {}, the CSG object is missed by such rays", error);
+                }
+                None
+            }
+        }
+    }
+
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Result<Crossings, SceneError> {
+        self.walk(ray, t_min, t_max, false)
+    }
+
+    fn scale(&mut self, _l: f64) {}
+
+    // hits carry the normal of the surface they come from
+    fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        center(&self.aabb.min, &self.aabb.max)
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.aabb.min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.aabb.max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, _sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        vec![]
+    }
+
+    // Only the left solid's, hits carry the material of the surface they come
+    // from and material_ids lists both
+    fn get_material_id(&self) -> usize {
+        self.left.get_material_id()
+    }
+
+    fn material_ids(&self) -> Vec<usize> {
+        let mut ids = self.left.material_ids();
+        ids.extend(self.right.material_ids());
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometric_object::{Cylinder, Sphere, Torus};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn operations_keep_the_right_intervals() {
+        // unit spheres centred at x = -0.5 and x = 0.5 once scaled
+        let sphere = |x| {
+            Arc::new(Sphere::new(0, 1.0, Point3::new(1.0 - x, 1.0, 1.0), 2.0))
+                as Arc<dyn Geometry + Send + Sync>
+        };
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::x());
+        let cases = [
+            (Csg::union(sphere(-0.5), sphere(0.5)), [3.5, 6.5]),
+            (Csg::intersection(sphere(-0.5), sphere(0.5)), [4.5, 5.5]),
+            (Csg::difference(sphere(-0.5), sphere(0.5)), [3.5, 4.5]),
+        ];
+        for (csg, expected) in cases {
+            let hits = csg
+                .unwrap()
+                .crossings(&ray, 0.0, f64::INFINITY)
+                .unwrap()
+                .hits;
+            assert_eq!(hits.len(), 2);
+            for (hit, t) in hits.iter().zip(expected) {
+                assert!((hit.dist - t).abs() < 1e-9, "{} {}", hit.dist, t);
+            }
+            assert!(hits[0].normal.dot(&ray.dir) < 0.0 && hits[1].normal.dot(&ray.dir) > 0.0);
+        }
+    }
+
+    #[test]
+    fn hit_carries_the_material_of_the_carved_surface() {
+        let sphere = |material_id, z| {
+            Arc::new(Sphere::new(material_id, 1.0, Point3::new(1.0, 1.0, z), 2.0))
+                as Arc<dyn Geometry + Send + Sync>
+        };
+        // the front of the left sphere is cut away by the right one
+        let csg = Csg::difference(sphere(1, 1.0), sphere(2, 0.0)).unwrap();
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::z());
+        let hit = csg.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 5.0).abs() < 1e-9);
+        assert_eq!(hit.material_id, 2);
+        assert!(hit.normal.dot(&ray.dir) < 0.0 && hit.geometric_normal.dot(&ray.dir) < 0.0);
+        assert!(csg.intersects(&ray, 0.0, 4.0).is_none());
+        assert_eq!(csg.material_ids(), vec![1, 2]);
+    }
+
+    #[test]
+    fn nested_csg_knows_the_ray_starts_inside() {
+        let sphere = |x| {
+            Arc::new(Sphere::new(0, 1.0, Point3::new(x, 1.0, 1.0), 2.0))
+                as Arc<dyn Geometry + Send + Sync>
+        };
+        let inner = Arc::new(Csg::union(sphere(1.0), sphere(2.0)).unwrap());
+        let outer = Csg::intersection(inner, sphere(1.0)).unwrap();
+        // from the shared centre along z, leaving at the sphere's surface
+        let ray = Ray::new(Point3::origin(), Vec3::z());
+        let crossings = outer.crossings(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(crossings.starts_inside);
+        assert_eq!(crossings.hits.len(), 1);
+        assert!((crossings.hits[0].dist - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn open_operands_are_rejected() {
+        let sphere =
+            Arc::new(Sphere::new(0, 1.0, Point3::origin(), 2.0)) as Arc<dyn Geometry + Send + Sync>;
+        let cylinder = |capped: bool, phi_max| {
+            let cylinder = Cylinder::new(0, Point3::origin(), Vec3::y(), 1.0, 1.0, 2.0);
+            let cylinder = cylinder.with_sweep(phi_max);
+            Arc::new(if capped {
+                cylinder.with_caps()
+            } else {
+                cylinder
+            }) as Arc<dyn Geometry + Send + Sync>
+        };
+        assert!(Csg::union(sphere.clone(), cylinder(true, 2.0 * PI)).is_ok());
+        assert!(Csg::union(sphere.clone(), cylinder(false, 2.0 * PI)).is_err());
+        assert!(Csg::union(cylinder(true, PI), sphere.clone()).is_err());
+
+        let torus = Torus::new(0, Point3::origin(), Vec3::y(), 1.0, 0.2, 2.0).with_sweep(PI);
+        assert!(Csg::union(sphere.clone(), Arc::new(torus)).is_err());
+        let torus = Torus::new(0, Point3::origin(), Vec3::y(), 1.0, 0.2, 2.0).with_sweep(PI);
+        assert!(Csg::union(sphere, Arc::new(torus.with_caps())).is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+use nalgebra::{center, Matrix4, Point2, Point3};
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::error::SceneError;
+use crate::geometric_object::{scale_direction, scale_point, Geometry, Instance};
+use crate::model::Vec3;
+use crate::ray::{error_bound, HitRecord, Ray};
+use crate::sampler::get_square_sampler;
+
+// Axis aligned box, named so it doesn't shadow std's Box. Oriented boxes are
+// an Instance of one, see `with_transform`
+pub struct Cuboid {
+    min: Point3<f64>,
+    max: Point3<f64>,
+    material_id: usize,
+}
+
+impl Cuboid {
+    pub fn new(material_id: usize, min: Point3<f64>, max: Point3<f64>, scale: f64) -> Cuboid {
+        let mut cuboid = Cuboid {
+            min,
+            max,
+            material_id,
+        };
+        cuboid.scale(scale);
+        cuboid
+    }
+
+    // transform in scene units applied about the center of the box, e.g. a
+    // rotation to turn it in place
+    pub fn with_transform(
+        self,
+        transform: Matrix4<f64>,
+        scale: f64,
+    ) -> Result<Instance, SceneError> {
+        // the center back in scene units, undoing scale_point
+        let c = (scale_direction(self.get_center().coords) + Vec3::repeat(1.0)) * (scale / 2.0);
+        let about_center = Matrix4::new_translation(&c) * transform * Matrix4::new_translation(&-c);
+        Instance::new(Arc::new(self), about_center, scale)
+    }
+
+    fn face_areas(&self) -> [f64; 3] {
+        let d = self.max - self.min;
+        [d.y * d.z, d.x * d.z, d.x * d.y]
+    }
+
+    fn face_normal(axis: usize, sign: f64) -> Vec3 {
+        let mut normal = Vec3::zeros();
+        normal[axis] = sign;
+        normal
+    }
+}
+
+impl Geometry for Cuboid {
+    // slabs like AABB::intersects, keeping the axes the ray enters and leaves by
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t0 = f64::NEG_INFINITY;
+        let mut t1 = f64::INFINITY;
+        let mut enter = (0, 0.0);
+        let mut leave = (0, 0.0);
+        for i in 0..3 {
+            let inv_d = ray.dir[i].recip();
+            let ta = (self.min[i] - ray.origin[i]) * inv_d;
+            let tb = (self.max[i] - ray.origin[i]) * inv_d;
+            let (near, far, sign) = if ta < tb {
+                (ta, tb, -1.0)
+            } else {
+                (tb, ta, 1.0)
+            };
+            if near > t0 {
+                t0 = near;
+                enter = (i, sign);
+            }
+            if far < t1 {
+                t1 = far;
+                leave = (i, -sign);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        let (t, (axis, sign)) = if t0 >= t_min {
+            (t0, enter)
+        } else {
+            (t1, leave)
+        };
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // on the face the hit coordinate is exact
+        let mut hit_point = ray.get_point(t);
+        hit_point[axis] = if sign > 0.0 {
+            self.max[axis]
+        } else {
+            self.min[axis]
+        };
+        let mut error = error_bound(&hit_point, 7);
+        error[axis] = 0.0;
+        let normal = Cuboid::face_normal(axis, sign);
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let local = (hit_point - self.min).component_div(&(self.max - self.min));
+        Some(HitRecord {
+            dist: t,
+            hit_point,
+            normal,
+            geometric_normal: normal,
+            error,
+            uv: Point2::new(local[a], local[b]),
+            vertex_color: None,
+            material_id: self.material_id,
+        })
+    }
+
+    // flipping the x and y axes swaps their min and max
+    fn scale(&mut self, l: f64) {
+        let a = scale_point(self.min, l);
+        let b = scale_point(self.max, l);
+        self.min = a.inf(&b);
+        self.max = a.sup(&b);
+    }
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        // the face the point is closest to
+        let (axis, sign, _) = (0..3)
+            .flat_map(|i| {
+                [
+                    (i, -1.0, (p[i] - self.min[i]).abs()),
+                    (i, 1.0, (p[i] - self.max[i]).abs()),
+                ]
+            })
+            .fold(
+                (0, -1.0, f64::INFINITY),
+                |a, b| if b.2 < a.2 { b } else { a },
+            );
+        Cuboid::face_normal(axis, sign)
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        center(&self.min, &self.max)
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    // faces are picked in proportion to their area
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        let areas = self.face_areas();
+        let total = 2.0 * areas.iter().sum::<f64>();
+        let mut rng = thread_rng();
+        get_square_sampler(sample_points_sqrt)
+            .map(|p| {
+                let mut pick = rng.gen::<f64>() * total;
+                let mut face = 5;
+                for f in 0..6 {
+                    if pick < areas[f / 2] {
+                        face = f;
+                        break;
+                    }
+                    pick -= areas[f / 2];
+                }
+                let axis = face / 2;
+                let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+                let mut point = self.min;
+                if face % 2 == 1 {
+                    point[axis] = self.max[axis];
+                }
+                point[a] += p.x * (self.max[a] - self.min[a]);
+                point[b] += p.y * (self.max[b] - self.min[b]);
+                point
+            })
+            .collect()
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn area(&self) -> f64 {
+        2.0 * self.face_areas().iter().sum::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the unit cube about the origin once scaled
+    fn cube() -> Cuboid {
+        Cuboid::new(
+            0,
+            Point3::new(0.5, 0.5, 0.5),
+            Point3::new(1.5, 1.5, 1.5),
+            2.0,
+        )
+    }
+
+    #[test]
+    fn hit_is_on_the_face_entered_or_left() {
+        let cube = cube();
+        let ray = Ray::new(Point3::new(0.1, -0.2, -2.0), Vec3::z());
+        let hit = cube.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 1.5).abs() < 1e-12);
+        assert_eq!(hit.hit_point.z, -0.5);
+        assert_eq!(hit.normal, -Vec3::z());
+        assert!((hit.uv - Point2::new(0.6, 0.3)).norm() < 1e-12);
+        // starting inside, the ray leaves by the opposite face
+        let hit = cube.intersects(&ray, 2.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 2.5).abs() < 1e-12);
+        assert_eq!(hit.normal, Vec3::z());
+        let beside = Ray::new(Point3::new(0.6, 0.0, -2.0), Vec3::z());
+        assert!(cube.intersects(&beside, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn samples_lie_on_the_faces() {
+        let cube = cube();
+        for p in cube.get_samples(10) {
+            let on_face = (0..3).any(|i| (p[i].abs() - 0.5).abs() < 1e-12);
+            assert!(on_face && (0..3).all(|i| p[i].abs() <= 0.5 + 1e-12));
+            let n = cube.normal(&p);
+            assert!((p.coords.dot(&n) - 0.5).abs() < 1e-12);
+        }
+        assert!((cube.area() - 6.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,205 @@
+use nalgebra::{Point2, Point3};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{
+    cap_root, nearer, nearest_root, surface_distance, sweep_angle, Frame, Geometry,
+};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+use crate::solver::solve_quadratic;
+
+// Cylinder standing on its base center along the axis. Open unless capped,
+// caps close the ends along the axis
+pub struct Cylinder {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material_id: usize,
+}
+
+impl Cylinder {
+    pub fn new(
+        material_id: usize,
+        base: Point3<f64>,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        scale: f64,
+    ) -> Cylinder {
+        let mut cylinder = Cylinder {
+            frame: Frame::new(base, axis),
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            material_id,
+        };
+        cylinder.scale(scale);
+        cylinder
+    }
+
+    // only the part swept from 0 to phi_max radians around the axis
+    pub fn with_sweep(mut self, phi_max: f64) -> Cylinder {
+        self.phi_max = phi_max;
+        self
+    }
+
+    pub fn with_caps(mut self) -> Cylinder {
+        self.capped = true;
+        self
+    }
+
+    fn in_sweep(&self, p: &Point3<f64>) -> bool {
+        sweep_angle(p) <= self.phi_max
+    }
+
+    // x^2 + z^2 = r^2
+    fn side_distance(&self, p: &Point3<f64>) -> f64 {
+        let r2 = p.x * p.x + p.z * p.z;
+        let f = r2 - self.radius * self.radius;
+        surface_distance(f, r2 + self.radius * self.radius, 2.0 * r2.sqrt(), 3)
+    }
+
+    fn cap_distance(&self, p: &Point3<f64>) -> f64 {
+        p.y.abs().min((p.y - self.height).abs())
+    }
+}
+
+impl Geometry for Cylinder {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = self.frame.to_local(ray);
+        let (o, d) = (local.origin, local.dir);
+        let roots = solve_quadratic([
+            o.x * o.x + o.z * o.z - self.radius * self.radius,
+            2.0 * (o.x * d.x + o.z * d.z),
+            d.x * d.x + d.z * d.z,
+        ]);
+        let side = nearest_root(&local, roots, t_min, t_max, |p| {
+            p.y >= 0.0 && p.y <= self.height && self.in_sweep(p)
+        })
+        .map(|(t, p)| {
+            let uv = Point2::new(sweep_angle(&p) / self.phi_max, p.y / self.height);
+            (t, Vec3::new(p.x, 0.0, p.z), uv, self.side_distance(&p))
+        });
+
+        let caps = if self.capped {
+            let mut roots = cap_root(&local, 0.0);
+            roots.extend(cap_root(&local, self.height));
+            nearest_root(&local, roots, t_min, t_max, |p| {
+                p.x * p.x + p.z * p.z <= self.radius * self.radius && self.in_sweep(p)
+            })
+        } else {
+            None
+        };
+        let cap = caps.map(|(t, p)| {
+            let r = (p.x * p.x + p.z * p.z).sqrt();
+            let uv = Point2::new(sweep_angle(&p) / self.phi_max, r / self.radius);
+            let normal = if p.y < self.height / 2.0 { -1.0 } else { 1.0 };
+            (t, Vec3::new(0.0, normal, 0.0), uv, self.cap_distance(&p))
+        });
+
+        Some(
+            self.frame
+                .hit_record(ray, nearer(side, cap)?, self.material_id),
+        )
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.frame.scale(l);
+        self.radius = (self.radius * 2.0) / l;
+        self.height = (self.height * 2.0) / l;
+    }
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let p = self.frame.to_local_point(p);
+        let on_cap = self.capped && self.cap_distance(&p) < self.side_distance(&p);
+        let normal = if on_cap {
+            Vec3::new(0.0, p.y - self.height / 2.0, 0.0)
+        } else {
+            Vec3::new(p.x, 0.0, p.z)
+        };
+        self.frame.to_world(&normal).normalize()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.frame
+            .to_world_point(&Point3::new(0.0, self.height / 2.0, 0.0))
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.get_bounding_box().min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.get_bounding_box().max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        self.frame.bounding_box(
+            Point3::new(-self.radius, 0.0, -self.radius),
+            Point3::new(self.radius, self.height, self.radius),
+        )
+    }
+
+    // the side only, its area is uniform in angle and height
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        self.frame.sample_surface(sample_points_sqrt, 1.0, |s| {
+            let phi = s.x * self.phi_max;
+            let p = Point3::new(
+                self.radius * phi.cos(),
+                s.y * self.height,
+                self.radius * phi.sin(),
+            );
+            (p, 1.0)
+        })
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    // partial sweeps are open along their cut, caps only close the ends
+    fn is_closed(&self) -> bool {
+        self.capped && self.phi_max >= 2.0 * PI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // radius 0.5 from y = 0 down to y = -1 once scaled, the y axis is flipped
+    fn cylinder() -> Cylinder {
+        Cylinder::new(0, Point3::new(1.0, 1.0, 1.0), Vec3::y(), 0.5, 1.0, 2.0)
+    }
+
+    #[test]
+    fn ray_across_the_side_crosses_twice() {
+        let ray = Ray::new(Point3::new(-2.0, -0.5, 0.0), Vec3::x());
+        let crossings = cylinder().crossings(&ray, 0.0, f64::INFINITY).unwrap();
+        let hits = crossings.hits;
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].dist - 1.5).abs() < 1e-9 && (hits[1].dist - 2.5).abs() < 1e-9);
+        assert!(hits[0].normal.dot(&ray.dir) < 0.0 && hits[1].normal.dot(&ray.dir) > 0.0);
+        // started between the walls
+        let crossings = cylinder().crossings(&ray, 2.0, f64::INFINITY).unwrap();
+        assert!(crossings.starts_inside);
+        assert_eq!(crossings.hits.len(), 1);
+    }
+
+    #[test]
+    fn caps_are_crossed_along_the_axis() {
+        let ray = Ray::new(Point3::new(0.1, 1.0, 0.2), -Vec3::y());
+        assert!(cylinder().intersects(&ray, 0.0, f64::INFINITY).is_none());
+        let capped = cylinder().with_caps();
+        assert!(capped.is_closed() && !cylinder().is_closed());
+        let hits = capped.crossings(&ray, 0.0, f64::INFINITY).unwrap().hits;
+        assert_eq!(hits.len(), 2);
+        assert!((hits[0].dist - 1.0).abs() < 1e-9 && (hits[1].dist - 2.0).abs() < 1e-9);
+        assert!((hits[0].normal - Vec3::y()).norm() < 1e-9);
+        assert!((hits[1].normal + Vec3::y()).norm() < 1e-9);
+    }
+}
@@ -0,0 +1,155 @@
+use nalgebra::{Point2, Point3};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{
+    plane_axes, project_to_plane, scale_direction, scale_point, Geometry,
+};
+use crate::model::Vec3;
+use crate::ray::{error_bound, HitRecord, Ray};
+use crate::sampler::get_disk_sampler;
+
+pub struct Disk {
+    center: Point3<f64>,
+    normal: Vec3,
+    radius: f64,
+    material_id: usize,
+}
+
+impl Disk {
+    pub fn new(
+        material_id: usize,
+        center: Point3<f64>,
+        normal: Vec3,
+        radius: f64,
+        scale: f64,
+    ) -> Disk {
+        let mut disk = Disk {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material_id,
+        };
+        disk.scale(scale);
+        disk
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+impl Geometry for Disk {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.center - ray.origin).dot(&self.normal) / ray.dir.dot(&self.normal);
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+        let (hit_point, error) = project_to_plane(ray.get_point(t), &self.center, &self.normal);
+        let d = hit_point - self.center;
+        let r = d.norm();
+        if r > self.radius {
+            return None;
+        }
+        // u goes around the disk, v from the center to the rim
+        let (u, v) = plane_axes(&self.normal);
+        let phi = d.dot(&v).atan2(d.dot(&u));
+        Some(HitRecord {
+            dist: t,
+            hit_point,
+            normal: self.normal,
+            geometric_normal: self.normal,
+            error,
+            uv: Point2::new(0.5 + phi / (2.0 * PI), r / self.radius),
+            vertex_color: None,
+            material_id: self.material_id,
+        })
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.center = scale_point(self.center, l);
+        self.normal = scale_direction(self.normal);
+        self.radius = (self.radius * 2.0) / l;
+    }
+
+    fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+        self.normal
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.center
+    }
+
+    // the disk extends radius * sin(angle between the axis and the normal),
+    // padded by the rounding of that extent
+    fn get_min_point(&self) -> Point3<f64> {
+        let n = self.normal;
+        let extent = Vec3::new(1.0 - n.x * n.x, 1.0 - n.y * n.y, 1.0 - n.z * n.z)
+            .map(|e| self.radius * e.max(0.0).sqrt());
+        let min = self.center - extent;
+        min - error_bound(&min, 6)
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.center + (self.center - self.get_min_point())
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        let (u, v) = plane_axes(&self.normal);
+        get_disk_sampler(sample_points_sqrt)
+            .map(|(_, p)| self.center + (u * p.x + v * p.y) * self.radius)
+            .collect()
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_stop_at_the_rim() {
+        // radius 0.5 at the origin once scaled, facing z
+        let disk = Disk::new(0, Point3::new(1.0, 1.0, 1.0), Vec3::z(), 0.5, 2.0);
+        let ray = |x: f64| Ray::new(Point3::new(x, 0.0, -1.0), Vec3::z());
+        let hit = disk.intersects(&ray(0.25), 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 1.0).abs() < 1e-12);
+        assert!((hit.uv.y - 0.5).abs() < 1e-12);
+        assert!(disk.intersects(&ray(0.55), 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn tilted_disk_is_bounded() {
+        let disk = Disk::new(
+            0,
+            Point3::new(3.0, 2.0, 1.0),
+            Vec3::new(1.0, 2.0, 2.0),
+            1.5,
+            4.0,
+        );
+        let aabb = disk.get_bounding_box();
+        let samples = disk.get_samples(8);
+        assert_eq!(samples.len(), 64);
+        for p in samples {
+            assert!((0..3).all(|i| aabb.min[i] <= p[i] && p[i] <= aabb.max[i]));
+        }
+        // the extent along x shrinks with the tilt of the normal towards it
+        let extent = aabb.max - aabb.min;
+        assert!(extent.x < 2.0 * disk.radius());
+    }
+}
@@ -0,0 +1,173 @@
+use nalgebra::{Point2, Point3};
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{plane_axes, scale_direction, scale_point};
+use crate::model::Vec3;
+use crate::ray::{error_bound, gamma, HitRecord, Ray};
+
+// Orthonormal frame of an object of revolution. Local y runs along the axis,
+// sweep angles start at local x and turn towards local z
+pub struct Frame {
+    origin: Point3<f64>,
+    u: Vec3,
+    w: Vec3,
+    v: Vec3,
+}
+
+impl Frame {
+    pub fn new(origin: Point3<f64>, axis: Vec3) -> Frame {
+        let w = axis.normalize();
+        let (u, v) = plane_axes(&w);
+        Frame { origin, u, w, v }
+    }
+
+    pub fn scale(&mut self, l: f64) {
+        *self = Frame::new(scale_point(self.origin, l), scale_direction(self.w));
+    }
+
+    pub fn to_local(&self, ray: &Ray) -> Ray {
+        let o = ray.origin - self.origin;
+        Ray::new(
+            Point3::new(o.dot(&self.u), o.dot(&self.w), o.dot(&self.v)),
+            Vec3::new(
+                ray.dir.dot(&self.u),
+                ray.dir.dot(&self.w),
+                ray.dir.dot(&self.v),
+            ),
+        )
+    }
+
+    pub fn to_local_point(&self, p: &Point3<f64>) -> Point3<f64> {
+        let o = p - self.origin;
+        Point3::new(o.dot(&self.u), o.dot(&self.w), o.dot(&self.v))
+    }
+
+    pub fn to_world_point(&self, p: &Point3<f64>) -> Point3<f64> {
+        self.origin + self.to_world(&p.coords)
+    }
+
+    pub fn to_world(&self, d: &Vec3) -> Vec3 {
+        self.u * d.x + self.w * d.y + self.v * d.z
+    }
+
+    // box around the transformed corners of a local box
+    pub fn bounding_box(&self, min: Point3<f64>, max: Point3<f64>) -> AABB {
+        let corners: Vec<Point3<f64>> = (0..8)
+            .map(|i| {
+                self.to_world_point(&Point3::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                ))
+            })
+            .collect();
+        let min = corners.iter().fold(corners[0], |a, b| a.inf(b));
+        let max = corners.iter().fold(corners[0], |a, b| a.sup(b));
+        AABB::new(min, max)
+    }
+
+    // The error covers the rounding of the hit point, of taking the ray into
+    // the frame, and how far the local point was found off the surface
+    pub fn hit_record(&self, ray: &Ray, hit: LocalHit, material_id: usize) -> HitRecord {
+        let (t, local_normal, uv, off_surface) = hit;
+        let hit_point = ray.get_point(t);
+        let normal = self.to_world(&local_normal).normalize();
+        let to_local = gamma(6) * ((ray.origin - self.origin).norm() + t.abs() * ray.dir.norm());
+        HitRecord {
+            dist: t,
+            hit_point,
+            normal,
+            geometric_normal: normal,
+            error: error_bound(&hit_point, 7) + normal.abs() * off_surface + Vec3::repeat(to_local),
+            uv,
+            vertex_color: None,
+            material_id,
+        }
+    }
+
+    // Area weighted points, `surface` maps a point of the unit square to a
+    // local point and its area density, which must not exceed `max_density`
+    pub fn sample_surface(
+        &self,
+        n: usize,
+        max_density: f64,
+        surface: impl Fn(Point2<f64>) -> (Point3<f64>, f64),
+    ) -> Vec<Point3<f64>> {
+        let mut rng = thread_rng();
+        let mut samples = Vec::with_capacity(n * n);
+        while samples.len() < n * n {
+            let (p, density) = surface(Point2::new(rng.gen(), rng.gen()));
+            if rng.gen::<f64>() * max_density <= density {
+                samples.push(self.to_world_point(&p));
+            }
+        }
+        samples
+    }
+}
+
+// angle around the local y axis in [0, 2π)
+pub fn sweep_angle(p: &Point3<f64>) -> f64 {
+    let phi = p.z.atan2(p.x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+// nearest root inside (t_min, t_max) whose local hit point passes the test
+pub fn nearest_root(
+    local: &Ray,
+    roots: Vec<f64>,
+    t_min: f64,
+    t_max: f64,
+    accept: impl Fn(&Point3<f64>) -> bool,
+) -> Option<(f64, Point3<f64>)> {
+    roots
+        .into_iter()
+        .filter(|t| *t > t_min && *t < t_max)
+        .map(|t| (t, local.get_point(t)))
+        .filter(|(_, p)| accept(p))
+        .fold(
+            None,
+            |nearest: Option<(f64, Point3<f64>)>, hit| match nearest {
+                Some(n) if n.0 <= hit.0 => Some(n),
+                _ => Some(hit),
+            },
+        )
+}
+
+// root of the plane at height y across the local axis
+pub fn cap_root(local: &Ray, y: f64) -> Vec<f64> {
+    if local.dir.y == 0.0 {
+        vec![]
+    } else {
+        vec![(y - local.origin.y) / local.dir.y]
+    }
+}
+
+// (distance, local normal, uv, how far the local point may lie off the
+// surface) of a hit on one part of a surface
+pub type LocalHit = (f64, Vec3, Point2<f64>, f64);
+
+// First order distance from a point to the surface f = 0, given f there, the
+// sum of the magnitudes of the terms it adds up and the length of its
+// gradient. f is only known up to the rounding of its n operations. Singular
+// points like a cone's apex have no such estimate and are left to the
+// rounding of the point itself
+pub fn surface_distance(f: f64, terms: f64, gradient: f64, n: u32) -> f64 {
+    if gradient > 0.0 {
+        (f.abs() + gamma(n) * terms) / gradient
+    } else {
+        0.0
+    }
+}
+
+pub fn nearer(a: Option<LocalHit>, b: Option<LocalHit>) -> Option<LocalHit> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
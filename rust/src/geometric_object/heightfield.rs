@@ -0,0 +1,415 @@
+use nalgebra::{Matrix3, Matrix4, Point2, Point3, Vector4};
+
+use crate::aabb::AABB;
+use crate::error::SceneError;
+use crate::geometric_object::Geometry;
+use crate::model::Vec3;
+use crate::ray::{gamma, transform_error, HitRecord, Ray};
+
+// grid space is measured in cells, so this is a fixed fraction of one
+const EPSILON: f64 = 1e-6;
+
+// Terrain over a grid of height samples. Rays are traced in grid space, where
+// cells are unit squares and y is the sample value, through a quadtree of the
+// lowest and highest sample under each node. Cells are split into two
+// triangles only when a ray reaches them
+pub struct Heightfield {
+    heights: Vec<f32>,
+    width: usize,               // samples along x
+    depth: usize,               // samples along z
+    levels: Vec<Vec<[f32; 2]>>, // min and max per node, cells first
+    origin: Point3<f64>,        // corner in scene units
+    size: Vec3,                 // extent along x and z, height of a sample valued 1
+    to_grid: Matrix4<f64>,
+    to_world: Matrix4<f64>,
+    aabb: AABB,
+    material_id: usize,
+}
+
+impl Heightfield {
+    pub fn new(
+        material_id: usize,
+        heights: Vec<f32>,
+        width: usize,
+        origin: Point3<f64>,
+        size: Vec3,
+        scale: f64,
+    ) -> Result<Heightfield, SceneError> {
+        let depth = heights.len().checked_div(width).unwrap_or(0);
+        if width < 2 || depth < 2 || width * depth != heights.len() {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: format!(
+                    "heightfield needs at least 2x2 samples, got {} in rows of {}",
+                    heights.len(),
+                    width
+                ),
+            });
+        }
+        if size.iter().any(|s| *s == 0.0 || !s.is_finite()) {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: "heightfield size must be finite and non zero".to_owned(),
+            });
+        }
+        let mut heightfield = Heightfield {
+            levels: build_levels(&heights, width, depth),
+            heights,
+            width,
+            depth,
+            origin,
+            size,
+            to_grid: Matrix4::identity(),
+            to_world: Matrix4::identity(),
+            aabb: AABB::new(Point3::origin(), Point3::origin()),
+            material_id,
+        };
+        heightfield.scale(scale);
+        Ok(heightfield)
+    }
+
+    fn height(&self, x: usize, z: usize) -> f64 {
+        self.heights[z * self.width + x] as f64
+    }
+
+    // grid space normal from central differences, one sided at the border
+    fn vertex_normal(&self, x: usize, z: usize) -> Vec3 {
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (z0, z1) = (z.saturating_sub(1), (z + 1).min(self.depth - 1));
+        let dx = (self.height(x1, z) - self.height(x0, z)) / (x1 - x0) as f64;
+        let dz = (self.height(x, z1) - self.height(x, z0)) / (z1 - z0) as f64;
+        Vec3::new(-dx, 1.0, -dz)
+    }
+
+    // node bounds in grid space, padded so flat ground still has a box to hit
+    fn node_box(&self, level: usize, i: usize, j: usize) -> AABB {
+        let span = 1 << level;
+        let [min, max] = self.levels[level][j * self.level_width(level) + i];
+        let pad = Vec3::repeat(EPSILON);
+        AABB::new(
+            Point3::new((i * span) as f64, min as f64, (j * span) as f64) - pad,
+            Point3::new(
+                ((i + 1) * span).min(self.width - 1) as f64,
+                max as f64,
+                ((j + 1) * span).min(self.depth - 1) as f64,
+            ) + pad,
+        )
+    }
+
+    fn level_width(&self, level: usize) -> usize {
+        ((self.width - 2) >> level) + 1
+    }
+
+    fn level_depth(&self, level: usize) -> usize {
+        ((self.depth - 2) >> level) + 1
+    }
+
+    // nearest hit on the two triangles of a cell, in grid space
+    fn intersect_cell(
+        &self,
+        ray: &Ray,
+        x: usize,
+        z: usize,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let corner = |dx: usize, dz: usize| {
+            let (x, z) = (x + dx, z + dz);
+            (
+                Point3::new(x as f64, self.height(x, z), z as f64),
+                self.vertex_normal(x, z),
+            )
+        };
+        let (c00, c10, c11, c01) = (corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1));
+        [(c00, c10, c11), (c00, c11, c01)]
+            .iter()
+            .filter_map(|(a, b, c)| {
+                let (t, u, v) = moller_trumbore(ray, &a.0, &b.0, &c.0)?;
+                if t <= t_min || t >= t_max {
+                    return None;
+                }
+                let w = 1.0 - u - v;
+                let hit_point = Point3::from(a.0.coords * w + b.0.coords * u + c.0.coords * v);
+                let error =
+                    (a.0.coords.abs() * w + b.0.coords.abs() * u + c.0.coords.abs() * v) * gamma(7);
+                Some(HitRecord {
+                    dist: t,
+                    hit_point,
+                    normal: a.1 * w + b.1 * u + c.1 * v,
+                    geometric_normal: (c.0 - a.0).cross(&(b.0 - a.0)),
+                    error,
+                    uv: Point2::new(
+                        hit_point.x / (self.width - 1) as f64,
+                        hit_point.z / (self.depth - 1) as f64,
+                    ),
+                    vertex_color: None,
+                    material_id: self.material_id,
+                })
+            })
+            .fold(None, |nearest: Option<HitRecord>, hit| match nearest {
+                Some(n) if n.dist <= hit.dist => Some(n),
+                _ => Some(hit),
+            })
+    }
+}
+
+// quadtree levels of [min, max], each node covering 2^level cells a side
+fn build_levels(heights: &[f32], width: usize, depth: usize) -> Vec<Vec<[f32; 2]>> {
+    let (mut w, mut d) = (width - 1, depth - 1);
+    let mut level: Vec<[f32; 2]> = (0..w * d)
+        .map(|c| {
+            let (x, z) = (c % w, c / w);
+            let corners = [
+                heights[z * width + x],
+                heights[z * width + x + 1],
+                heights[(z + 1) * width + x],
+                heights[(z + 1) * width + x + 1],
+            ];
+            corners
+                .iter()
+                .fold([f32::INFINITY, f32::NEG_INFINITY], |[lo, hi], h| {
+                    [lo.min(*h), hi.max(*h)]
+                })
+        })
+        .collect();
+    let mut levels = vec![];
+    loop {
+        let done = w == 1 && d == 1;
+        levels.push(level);
+        if done {
+            return levels;
+        }
+        let below = levels.last().unwrap();
+        let (pw, pd) = (w - w / 2, d - d / 2);
+        level = (0..pw * pd)
+            .map(|c| {
+                let (i, j) = (c % pw, c / pw);
+                let mut bounds = [f32::INFINITY, f32::NEG_INFINITY];
+                for z in 2 * j..(2 * j + 2).min(d) {
+                    for x in 2 * i..(2 * i + 2).min(w) {
+                        let [lo, hi] = below[z * w + x];
+                        bounds = [bounds[0].min(lo), bounds[1].max(hi)];
+                    }
+                }
+                bounds
+            })
+            .collect();
+        w = pw;
+        d = pd;
+    }
+}
+
+fn moller_trumbore(
+    ray: &Ray,
+    v0: &Point3<f64>,
+    v1: &Point3<f64>,
+    v2: &Point3<f64>,
+) -> Option<(f64, f64, f64)> {
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let h = ray.dir.cross(&e2);
+    let a = e1.dot(&h);
+    if a.abs() < 1e-12 {
+        return None;
+    }
+    let f = 1.0 / a;
+    let s = ray.origin - v0;
+    let u = f * s.dot(&h);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = f * ray.dir.dot(&q);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some((f * e2.dot(&q), u, v))
+}
+
+impl Geometry for Heightfield {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.aabb.intersects(ray, t_min, t_max) {
+            return None;
+        }
+        // the direction is not normalized so distances carry over
+        let grid_ray = Ray::new(
+            self.to_grid.transform_point(&ray.origin),
+            self.to_grid.transform_vector(&ray.dir),
+        );
+        // visit children nearest the ray origin first so far nodes get culled
+        let flip_x = grid_ray.dir.x < 0.0;
+        let flip_z = grid_ray.dir.z < 0.0;
+
+        let mut nearest: Option<HitRecord> = None;
+        let mut stack = vec![(self.levels.len() - 1, 0, 0)];
+        while let Some((level, i, j)) = stack.pop() {
+            let t_far = nearest.as_ref().map_or(t_max, |n| n.dist);
+            if !self
+                .node_box(level, i, j)
+                .intersects(&grid_ray, t_min, t_far)
+            {
+                continue;
+            }
+            if level == 0 {
+                if let Some(hit) = self.intersect_cell(&grid_ray, i, j, t_min, t_far) {
+                    nearest = Some(hit);
+                }
+                continue;
+            }
+            let (w, d) = (self.level_width(level - 1), self.level_depth(level - 1));
+            // pushed far to near
+            for k in (0..4).rev() {
+                let (dx, dz) = (k & 1, k >> 1);
+                let ci = 2 * i + if flip_x { 1 - dx } else { dx };
+                let cj = 2 * j + if flip_z { 1 - dz } else { dz };
+                if ci < w && cj < d {
+                    stack.push((level - 1, ci, cj));
+                }
+            }
+        }
+
+        let hit = nearest?;
+        Some(HitRecord {
+            hit_point: self.to_world.transform_point(&hit.hit_point),
+            normal: self.normal_to_world(&hit.normal),
+            geometric_normal: self.normal_to_world(&hit.geometric_normal),
+            error: transform_error(&self.to_world, &hit.hit_point, &hit.error),
+            ..hit
+        })
+    }
+
+    // maps scene units through the grid, both ways
+    fn scale(&mut self, l: f64) {
+        let cell = Vec3::new(
+            self.size.x / (self.width - 1) as f64,
+            self.size.y,
+            self.size.z / (self.depth - 1) as f64,
+        );
+        // inverse of scale_point
+        let unscale = Matrix4::new_translation(&Vec3::repeat(l / 2.0))
+            * Matrix4::new_scaling(l / 2.0)
+            * Matrix4::from_diagonal(&Vector4::new(-1.0, -1.0, 1.0, 1.0));
+        self.to_grid = Matrix4::new_nonuniform_scaling(&cell.map(f64::recip))
+            * Matrix4::new_translation(&-self.origin.coords)
+            * unscale;
+        self.to_world = self.to_grid.try_inverse().unwrap_or_else(Matrix4::identity);
+
+        let top = self.levels.last().unwrap()[0];
+        let corners: Vec<Point3<f64>> = (0..8)
+            .map(|i| {
+                self.to_world.transform_point(&Point3::new(
+                    if i & 1 == 0 {
+                        0.0
+                    } else {
+                        (self.width - 1) as f64
+                    },
+                    top[i >> 1 & 1] as f64,
+                    if i & 4 == 0 {
+                        0.0
+                    } else {
+                        (self.depth - 1) as f64
+                    },
+                ))
+            })
+            .collect();
+        let min = corners.iter().fold(corners[0], |a, b| a.inf(b));
+        let max = corners.iter().fold(corners[0], |a, b| a.sup(b));
+        self.aabb = AABB::new(min, max);
+    }
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let g = self.to_grid.transform_point(p);
+        let x = (g.x.round().max(0.0) as usize).min(self.width - 1);
+        let z = (g.z.round().max(0.0) as usize).min(self.depth - 1);
+        self.normal_to_world(&self.vertex_normal(x, z))
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        nalgebra::center(&self.aabb.min, &self.aabb.max)
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.aabb.min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.aabb.max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, _sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        vec![]
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+}
+
+impl Heightfield {
+    // normals transform with the inverse transpose
+    fn normal_to_world(&self, n: &Vec3) -> Vec3 {
+        let normal_matrix: Matrix3<f64> = self.to_grid.fixed_slice::<3, 3>(0, 0).transpose();
+        (normal_matrix * n).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 5x5 samples over a 2x2 scene square, sample value 1 is 2 high, so the
+    // grid's center is the normalized origin
+    fn heightfield(height: impl Fn(usize, usize) -> f32) -> Heightfield {
+        let heights = (0..5)
+            .flat_map(|z| (0..5).map(move |x| (x, z)))
+            .map(|(x, z)| height(x, z))
+            .collect();
+        Heightfield::new(0, heights, 5, Point3::origin(), Vec3::repeat(2.0), 2.0).unwrap()
+    }
+
+    // straight down the normalized y axis at (x, z)
+    fn ray(x: f64, z: f64) -> Ray {
+        Ray::new(Point3::new(x, 2.0, z), -Vec3::y())
+    }
+
+    #[test]
+    fn ramp_is_hit_at_its_interpolated_height() {
+        // half a unit high at the center, rising a quarter per sample along x
+        let ramp = heightfield(|x, _| x as f32 * 0.25);
+        let hit = ramp.intersects(&ray(0.0, 0.1), 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 2.0).abs() < 1e-9);
+        // normalized x runs against scene x
+        let hit = ramp
+            .intersects(&ray(-0.25, 0.1), 0.0, f64::INFINITY)
+            .unwrap();
+        assert!((hit.dist - 2.25).abs() < 1e-9);
+        assert!(ramp
+            .intersects(&ray(1.5, 0.1), 0.0, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn flat_ground_is_hit() {
+        let flat = heightfield(|_, _| 0.5);
+        for (x, z) in [(0.0, 0.0), (0.9, -0.9), (-0.3, 0.7)] {
+            let hit = flat.intersects(&ray(x, z), 0.0, f64::INFINITY).unwrap();
+            assert!((hit.dist - 2.0).abs() < 1e-9);
+            assert!((hit.normal.normalize().dot(&Vec3::y())).abs() > 1.0 - 1e-9);
+        }
+    }
+
+    #[test]
+    fn degenerate_grids_are_rejected() {
+        let new = |heights: Vec<f32>, width, size| {
+            Heightfield::new(0, heights, width, Point3::origin(), size, 2.0)
+        };
+        assert!(new(vec![0.0; 4], 1, Vec3::repeat(1.0)).is_err());
+        assert!(new(vec![0.0; 5], 2, Vec3::repeat(1.0)).is_err());
+        assert!(new(vec![0.0; 4], 2, Vec3::new(1.0, 0.0, 1.0)).is_err());
+        assert!(new(vec![0.0; 4], 2, Vec3::repeat(1.0)).is_ok());
+    }
+}
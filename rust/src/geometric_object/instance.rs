@@ -0,0 +1,250 @@
+use nalgebra::{Matrix3, Matrix4, Point3};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::error::SceneError;
+use crate::geometric_object::{scale_direction, scale_point, Crossings, Geometry};
+use crate::model::Vec3;
+use crate::ray::{transform_error, HitRecord, Ray};
+
+// A shared object placed with an affine transform. Rays are transformed into
+// object space, the ray direction is not normalized so hit distances carry over.
+// Transforms are given in scene units like the other constructors
+pub struct Instance {
+    object: Arc<dyn Geometry + Send + Sync>,
+    forward: Matrix4<f64>,
+    inverse: Matrix4<f64>,
+    aabb: AABB,
+    scale: f64,
+}
+
+impl Instance {
+    pub fn new(
+        object: Arc<dyn Geometry + Send + Sync>,
+        transform: Matrix4<f64>,
+        scale: f64,
+    ) -> Result<Instance, SceneError> {
+        Instance::placed(object, normalize_transform(&transform, scale), scale)
+    }
+
+    // `forward` is already in normalized units
+    fn placed(
+        object: Arc<dyn Geometry + Send + Sync>,
+        forward: Matrix4<f64>,
+        scale: f64,
+    ) -> Result<Instance, SceneError> {
+        let inverse = forward
+            .try_inverse()
+            .ok_or_else(|| SceneError::DegenerateGeometry {
+                file: None,
+                message: "instance transform is not invertible".to_owned(),
+            })?;
+        let aabb = transform_aabb(&object.get_bounding_box(), &forward);
+        Ok(Instance {
+            object,
+            forward,
+            inverse,
+            aabb,
+            scale,
+        })
+    }
+
+    // applies another transform after the current one
+    pub fn transform(self, transform: Matrix4<f64>) -> Result<Instance, SceneError> {
+        let forward = normalize_transform(&transform, self.scale) * self.forward;
+        Instance::placed(self.object, forward, self.scale)
+    }
+
+    pub fn translate(self, d: Vec3) -> Result<Instance, SceneError> {
+        self.transform(Matrix4::new_translation(&d))
+    }
+
+    // angles in radians about the x, y and z axes
+    pub fn rotate(self, angles: Vec3) -> Result<Instance, SceneError> {
+        self.transform(Matrix4::from_euler_angles(angles.x, angles.y, angles.z))
+    }
+
+    pub fn scale_by(self, s: Vec3) -> Result<Instance, SceneError> {
+        self.transform(Matrix4::new_nonuniform_scaling(&s))
+    }
+
+    fn to_object(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(&ray.origin),
+            self.inverse.transform_vector(&ray.dir),
+        )
+    }
+
+    // the object space hit point and its error are carried through the
+    // transform, the ray only gives the distance
+    fn to_world(&self, hit: HitRecord) -> HitRecord {
+        HitRecord {
+            hit_point: self.forward.transform_point(&hit.hit_point),
+            normal: self.normal_to_world(&hit.normal),
+            geometric_normal: self.normal_to_world(&hit.geometric_normal),
+            error: transform_error(&self.forward, &hit.hit_point, &hit.error),
+            ..hit
+        }
+    }
+
+    // normals transform with the inverse transpose
+    fn normal_to_world(&self, n: &Vec3) -> Vec3 {
+        let normal_matrix: Matrix3<f64> = self.inverse.fixed_slice::<3, 3>(0, 0).transpose();
+        (normal_matrix * n).normalize()
+    }
+}
+
+impl Geometry for Instance {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.object
+            .intersects(&self.to_object(ray), t_min, t_max)
+            .map(|hit| self.to_world(hit))
+    }
+
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Result<Crossings, SceneError> {
+        let crossings = self.object.crossings(&self.to_object(ray), t_min, t_max)?;
+        Ok(Crossings {
+            starts_inside: crossings.starts_inside,
+            hits: crossings
+                .hits
+                .into_iter()
+                .map(|hit| self.to_world(hit))
+                .collect(),
+        })
+    }
+
+    // the shared object is scaled once, not per instance
+    fn scale(&mut self, _l: f64) {}
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let n = self.object.normal(&self.inverse.transform_point(p));
+        self.normal_to_world(&n)
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.forward.transform_point(&self.object.get_center())
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.aabb.min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.aabb.max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        self.object
+            .get_samples(sample_points_sqrt)
+            .iter()
+            .map(|p| self.forward.transform_point(p))
+            .collect()
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.object.get_material_id()
+    }
+
+    fn material_ids(&self) -> Vec<usize> {
+        self.object.material_ids()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
+    }
+}
+
+// The transform seen in normalized units: scale_direction(A·scale_direction(d))
+// for the linear part, whose uniform scaling cancels, and the image of the
+// normalized origin for the translation
+fn normalize_transform(transform: &Matrix4<f64>, l: f64) -> Matrix4<f64> {
+    let mut normalized = Matrix4::identity();
+    for i in 0..3 {
+        let axis = scale_direction(Vec3::ith(i, 1.0));
+        let column = scale_direction(transform.transform_vector(&axis));
+        normalized.fixed_slice_mut::<3, 1>(0, i).copy_from(&column);
+    }
+    let origin = transform.transform_point(&Point3::from(Vec3::repeat(l / 2.0)));
+    normalized
+        .fixed_slice_mut::<3, 1>(0, 3)
+        .copy_from(&scale_point(origin, l).coords);
+    normalized
+}
+
+// box around the transformed corners of the object's box
+fn transform_aabb(aabb: &AABB, transform: &Matrix4<f64>) -> AABB {
+    let corners: Vec<Point3<f64>> = (0..8)
+        .map(|i| {
+            let corner = Point3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            );
+            transform.transform_point(&corner)
+        })
+        .collect();
+    let min = corners.iter().fold(corners[0], |a, b| a.inf(b));
+    let max = corners.iter().fold(corners[0], |a, b| a.sup(b));
+    AABB::new(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometric_object::{Cuboid, Sphere};
+
+    // unit sphere at the center of a scene of size 2, the normalized origin
+    fn sphere() -> Arc<dyn Geometry + Send + Sync> {
+        Arc::new(Sphere::new(0, 1.0, Point3::new(1.0, 1.0, 1.0), 2.0))
+    }
+
+    #[test]
+    fn transform_is_in_scene_units() {
+        let translation = Matrix4::new_translation(&Vec3::new(1.0, 0.0, 2.0));
+        let instance = Instance::new(sphere(), translation, 2.0).unwrap();
+        // scene x is flipped in normalized units
+        assert!((instance.get_center() - Point3::new(-1.0, 0.0, 2.0)).norm() < 1e-12);
+        let ray = Ray::new(Point3::new(-1.0, 0.0, -5.0), Vec3::z());
+        let hit = instance.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 6.0).abs() < 1e-9);
+        assert!((hit.normal + Vec3::z()).norm() < 1e-9);
+    }
+
+    #[test]
+    fn transforms_compose_in_scene_units() {
+        let chained = Instance::new(sphere(), Matrix4::identity(), 2.0)
+            .and_then(|i| i.scale_by(Vec3::new(2.0, 1.0, 1.0)))
+            .and_then(|i| i.translate(Vec3::new(0.0, 3.0, 0.0)))
+            .unwrap();
+        let single = Matrix4::new_translation(&Vec3::new(0.0, 3.0, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vec3::new(2.0, 1.0, 1.0));
+        let single = Instance::new(sphere(), single, 2.0).unwrap();
+        assert!((chained.forward - single.forward).norm() < 1e-12);
+    }
+
+    #[test]
+    fn cuboid_turns_about_its_center() {
+        let cuboid = Cuboid::new(
+            0,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 1.0, 1.0),
+            4.0,
+        );
+        let center = cuboid.get_center();
+        let turn = Matrix4::from_euler_angles(0.0, 0.0, std::f64::consts::FRAC_PI_2);
+        let instance = cuboid.with_transform(turn, 4.0).unwrap();
+        assert!((instance.get_center() - center).norm() < 1e-12);
+        let extent = instance.get_max_point() - instance.get_min_point();
+        assert!((extent - Vec3::new(0.5, 1.0, 0.5)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn singular_transform_is_rejected() {
+        let flat = Matrix4::new_nonuniform_scaling(&Vec3::new(1.0, 0.0, 1.0));
+        assert!(Instance::new(sphere(), flat, 2.0).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use nalgebra::{center, Point3};
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{Geometry, TriangleMesh};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+use crate::sampler::get_triangle_sampler;
+
+// Bottom level of the two-level hierarchy: built once per mesh and shared by
+// every Instance of it, the world's BvhNode is the top level over the objects.
+// Nodes refer to faces by index and test them against the mesh directly
+pub struct MeshBvh {
+    pub mesh: Arc<TriangleMesh>,
+    nodes: Vec<MeshNode>, // the root comes first, empty for a mesh without faces
+}
+
+enum MeshNode {
+    Face(AABB, usize),
+    Split(AABB, usize, usize), // children are indices into nodes
+}
+
+impl MeshNode {
+    fn aabb(&self) -> &AABB {
+        match self {
+            MeshNode::Face(aabb, _) | MeshNode::Split(aabb, _, _) => aabb,
+        }
+    }
+}
+
+impl MeshBvh {
+    pub fn new(mesh: Arc<TriangleMesh>) -> MeshBvh {
+        let mut faces: Vec<usize> = (0..mesh.faces.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * faces.len());
+        if !faces.is_empty() {
+            build(&mesh, &mut faces, &mut nodes);
+        }
+        MeshBvh { mesh, nodes }
+    }
+
+    fn intersects_node(&self, node: usize, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        match &self.nodes[node] {
+            MeshNode::Face(aabb, face) => {
+                if !aabb.intersects(ray, t_min, t_max) {
+                    return None;
+                }
+                self.mesh.intersect_face(*face, ray, t_min, t_max)
+            }
+            MeshNode::Split(aabb, left, right) => {
+                if !aabb.intersects(ray, t_min, t_max) {
+                    return None;
+                }
+                self.intersects_node(*left, ray, t_min, t_max).map_or_else(
+                    || self.intersects_node(*right, ray, t_min, t_max),
+                    |r1| {
+                        self.intersects_node(*right, ray, t_min, r1.dist)
+                            .or(Some(r1))
+                    },
+                )
+            }
+        }
+    }
+}
+
+// Splits the faces at the median along a random axis like BvhNode::new, sorting
+// the slice in place. Returns the index of the node pushed for it
+fn build(mesh: &TriangleMesh, faces: &mut [usize], nodes: &mut Vec<MeshNode>) -> usize {
+    let index = nodes.len();
+    if let [face] = faces {
+        nodes.push(MeshNode::Face(mesh.face_bounds(*face), *face));
+        return index;
+    }
+    let axis = thread_rng().gen_range(0..3);
+    faces.sort_by(|a, b| {
+        let (a, b) = (mesh.face_bounds(*a), mesh.face_bounds(*b));
+        a.min[axis].total_cmp(&b.min[axis])
+    });
+    // placeholder until the children are built
+    nodes.push(MeshNode::Face(
+        AABB::new(Point3::origin(), Point3::origin()),
+        0,
+    ));
+    let (left_faces, right_faces) = faces.split_at_mut(faces.len() / 2);
+    let left = build(mesh, left_faces, nodes);
+    let right = build(mesh, right_faces, nodes);
+    let aabb = AABB::get_surrounding_aabb(nodes[left].aabb(), nodes[right].aabb());
+    nodes[index] = MeshNode::Split(aabb, left, right);
+    index
+}
+
+impl Geometry for MeshBvh {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        self.intersects_node(0, ray, t_min, t_max)
+    }
+
+    // meshes are scaled as a whole when they are created
+    fn scale(&mut self, _l: f64) {}
+
+    fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        center(&self.get_min_point(), &self.get_max_point())
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.get_bounding_box().min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.get_bounding_box().max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        self.nodes
+            .first()
+            .map_or(AABB::new(Point3::origin(), Point3::origin()), |root| {
+                AABB::new(root.aabb().min, root.aabb().max)
+            })
+    }
+
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        (0..self.mesh.faces.len())
+            .flat_map(|f| get_triangle_sampler(sample_points_sqrt, self.mesh.vertices(f)))
+            .collect()
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.mesh.material_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // squares across the z axis, at z = 1, 2, ... once scaled
+    fn stack(layers: usize) -> MeshBvh {
+        let mut positions = vec![];
+        let mut faces = vec![];
+        for layer in 0..layers {
+            let z = (layer + 2) as f64;
+            let first = positions.len() as u32;
+            positions.extend([
+                Point3::new(0.0, 0.0, z),
+                Point3::new(2.0, 0.0, z),
+                Point3::new(2.0, 2.0, z),
+                Point3::new(0.0, 2.0, z),
+            ]);
+            faces.push([first, first + 1, first + 2]);
+            faces.push([first, first + 2, first + 3]);
+        }
+        MeshBvh::new(Arc::new(TriangleMesh::new(0, positions, faces, 2.0)))
+    }
+
+    #[test]
+    fn nearest_face_inside_the_range_is_hit() {
+        let bvh = stack(8);
+        let ray = Ray::new(Point3::new(0.1, 0.2, 0.0), Vec3::z());
+        let hit = bvh.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 1.0).abs() < 1e-9);
+        let hit = bvh.intersects(&ray, 1.5, f64::INFINITY).unwrap();
+        assert!((hit.dist - 2.0).abs() < 1e-9);
+        assert!(bvh.intersects(&ray, 0.0, 0.5).is_none());
+    }
+
+    #[test]
+    fn samples_cover_every_face() {
+        let bvh = stack(3);
+        let samples = bvh.get_samples(2);
+        assert_eq!(samples.len(), 6 * 4);
+        for layer in 1..=3 {
+            let on_layer = samples.iter().filter(|p| p.z == layer as f64).count();
+            assert_eq!(on_layer, 2 * 4);
+        }
+    }
+
+    #[test]
+    fn empty_mesh_bvh_is_never_hit() {
+        let mesh = Arc::new(TriangleMesh::new(0, vec![], vec![], 555.0));
+        let bvh = MeshBvh::new(mesh);
+        let ray = Ray::new(Point3::origin(), Vec3::z());
+        assert!(bvh.intersects(&ray, 0.0, f64::INFINITY).is_none());
+    }
+}
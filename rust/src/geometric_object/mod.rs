@@ -1,16 +1,47 @@
 use nalgebra::Point3;
 
 use crate::aabb::AABB;
+use crate::error::SceneError;
 use crate::model::Vec3;
-use crate::ray::{HitRecord, Ray};
+use crate::ray::{error_bound, next_float_up, offset_along, HitRecord, Ray};
 
 pub mod bvh_node;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+pub mod frame;
+pub mod heightfield;
+pub mod instance;
+pub mod mesh_bvh;
+pub mod paraboloid;
+pub mod plane;
+pub mod rectangle;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
 pub mod triangle;
+pub mod triangle_mesh;
 
 pub use bvh_node::*;
+pub use cone::*;
+pub use csg::*;
+pub use cuboid::*;
+pub use cylinder::*;
+pub use disk::*;
+pub use frame::*;
+pub use heightfield::*;
+pub use instance::*;
+pub use mesh_bvh::*;
+pub use paraboloid::*;
+pub use plane::*;
+pub use rectangle::*;
+pub use sdf::*;
 pub use sphere::*;
+pub use torus::*;
 pub use triangle::*;
+pub use triangle_mesh::*;
 
 pub trait Geometry {
     fn scale(&mut self, l: f64);
@@ -21,5 +52,168 @@ pub trait Geometry {
     fn get_max_point(&self) -> Point3<f64>;
     fn get_bounding_box(&self) -> AABB;
     fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>>;
+    // the object's own material, shading uses the one in the hit record
     fn get_material_id(&self) -> usize;
+
+    // every material the object's hits can carry, nested parts included
+    fn material_ids(&self) -> Vec<usize> {
+        vec![self.get_material_id()]
+    }
+
+    // surface area, only needed by the shapes area lights are made of
+    fn area(&self) -> f64 {
+        0.0
+    }
+
+    // whether the surface bounds a solid, which CSG needs of its operands
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    // Crossings of the surface between t_min and t_max, for closed solids
+    // which alternate between entering and leaving as CSG relies on. The first
+    // crossing tells whether the ray starts inside, even past t_max. Each
+    // search resumes where the ray leaves the error bounds of the previous
+    // hit, and more than MAX_CROSSINGS is an error rather than a partial list
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Result<Crossings, SceneError> {
+        let mut hits: Vec<HitRecord> = vec![];
+        let mut starts_inside = None;
+        let mut t = t_min;
+        loop {
+            let t_end = if starts_inside.is_some() {
+                t_max
+            } else {
+                f64::INFINITY
+            };
+            let hit = match self.intersects(ray, t, t_end) {
+                Some(hit) => hit,
+                None => break,
+            };
+            starts_inside.get_or_insert(hit.normal.dot(&ray.dir) > 0.0);
+            if hit.dist > t_max {
+                break;
+            }
+            if hits.len() == MAX_CROSSINGS {
+                return Err(SceneError::DegenerateGeometry {
+                    file: None,
+                    message: format!("a ray crosses a surface more than {} times", MAX_CROSSINGS),
+                });
+            }
+            let n = hit.geometric_normal;
+            let clearance = offset_along(&n, &hit.error).norm() / ray.dir.dot(&n).abs();
+            t = next_float_up(hit.dist + clearance);
+            hits.push(hit);
+        }
+        Ok(Crossings {
+            starts_inside: starts_inside.unwrap_or(false),
+            hits,
+        })
+    }
+}
+
+const MAX_CROSSINGS: usize = 64;
+
+// where a ray meets a closed solid, nearest first
+pub struct Crossings {
+    pub starts_inside: bool,
+    pub hits: Vec<HitRecord>,
+}
+
+// the same normalisation Geometry::scale applies to vertices
+pub fn scale_point(p: Point3<f64>, l: f64) -> Point3<f64> {
+    let p = p * (2.0 / l) - Vec3::repeat(1.0);
+    Point3::new(-p.x, -p.y, p.z)
+}
+
+pub fn scale_direction(d: Vec3) -> Vec3 {
+    Vec3::new(-d.x, -d.y, d.z)
+}
+
+// Moves a point found along a ray back onto the plane, leaving only the
+// rounding error of the projection rather than that of the ray distance
+pub fn project_to_plane(p: Point3<f64>, point: &Point3<f64>, normal: &Vec3) -> (Point3<f64>, Vec3) {
+    let projected = p - normal * (p - point).dot(normal);
+    (
+        projected,
+        error_bound(&projected, 7) + error_bound(point, 7),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point2;
+
+    // parallel planes one unit apart along z, without end
+    struct Layers;
+
+    impl Geometry for Layers {
+        fn scale(&mut self, _l: f64) {}
+
+        fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let t = t_min.max(0.0).ceil();
+            (t <= t_max).then(|| HitRecord {
+                dist: t,
+                hit_point: ray.get_point(t),
+                normal: -Vec3::z(),
+                geometric_normal: -Vec3::z(),
+                error: Vec3::zeros(),
+                uv: Point2::origin(),
+                vertex_color: None,
+                material_id: 0,
+            })
+        }
+
+        fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+            -Vec3::z()
+        }
+
+        fn get_center(&self) -> Point3<f64> {
+            Point3::origin()
+        }
+
+        fn get_min_point(&self) -> Point3<f64> {
+            Point3::origin()
+        }
+
+        fn get_max_point(&self) -> Point3<f64> {
+            Point3::origin()
+        }
+
+        fn get_bounding_box(&self) -> AABB {
+            AABB::new(Point3::origin(), Point3::origin())
+        }
+
+        fn get_samples(&self, _sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+            vec![]
+        }
+
+        fn get_material_id(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn crossings_enter_then_leave() {
+        let cuboid = Cuboid::new(
+            0,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            2.0,
+        );
+        let ray = Ray::new(Point3::new(0.5, 0.5, -2.0), Vec3::z());
+        let crossings = cuboid.crossings(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(!crossings.starts_inside);
+        let hits = crossings.hits;
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].normal.dot(&ray.dir) < 0.0 && hits[1].normal.dot(&ray.dir) > 0.0);
+    }
+
+    #[test]
+    fn too_many_crossings_is_an_error() {
+        let ray = Ray::new(Point3::origin(), Vec3::z());
+        assert!(Layers.crossings(&ray, 0.5, f64::INFINITY).is_err());
+        // the cap only counts crossings up to t_max
+        assert!(Layers.crossings(&ray, 0.5, 10.0).is_ok());
+    }
 }
@@ -0,0 +1,114 @@
+use nalgebra::{Point2, Point3};
+
+use crate::aabb::AABB;
+use crate::geometric_object::{project_to_plane, scale_direction, scale_point, Geometry};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+
+// Infinite plane through a point. Its bounding box is infinite, so the world
+// tests planes separately instead of putting them in the BVH
+pub struct Plane {
+    point: Point3<f64>,
+    normal: Vec3,
+    material_id: usize,
+}
+
+impl Plane {
+    pub fn new(material_id: usize, point: Point3<f64>, normal: Vec3, scale: f64) -> Plane {
+        let mut plane = Plane {
+            point,
+            normal: normal.normalize(),
+            material_id,
+        };
+        plane.scale(scale);
+        plane
+    }
+}
+
+impl Geometry for Plane {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.point - ray.origin).dot(&self.normal) / ray.dir.dot(&self.normal);
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+        let (hit_point, error) = project_to_plane(ray.get_point(t), &self.point, &self.normal);
+        // planar coordinates in world units
+        let (u, v) = plane_axes(&self.normal);
+        let d = hit_point - self.point;
+        Some(HitRecord {
+            dist: t,
+            hit_point,
+            normal: self.normal,
+            geometric_normal: self.normal,
+            error,
+            uv: Point2::new(d.dot(&u), d.dot(&v)),
+            vertex_color: None,
+            material_id: self.material_id,
+        })
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.point = scale_point(self.point, l);
+        self.normal = scale_direction(self.normal);
+    }
+
+    fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+        self.normal
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.point
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY)
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY)
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    // an infinite plane has no area to sample
+    fn get_samples(&self, _sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        vec![]
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+}
+
+// two unit vectors spanning the plane with the given normal
+pub fn plane_axes(normal: &Vec3) -> (Vec3, Vec3) {
+    let u = normal.cross(&Vec3::new(0.0072, 1.0, 0.0034)).normalize();
+    let v = normal.cross(&u);
+    (u, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_lies_on_the_plane() {
+        let plane = Plane::new(0, Point3::new(1.0, 1.0, 1.5), Vec3::new(0.3, 0.4, 1.0), 2.0);
+        let ray = Ray::new(Point3::new(0.1, -0.2, -3.0), Vec3::new(0.01, 0.02, 1.0));
+        let hit = plane.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        let offset = (hit.hit_point - plane.point).dot(&plane.normal);
+        assert!(offset.abs() <= hit.error.dot(&plane.normal.abs()));
+        assert!(plane.intersects(&ray, 0.0, hit.dist / 2.0).is_none());
+    }
+
+    #[test]
+    fn parallel_ray_misses() {
+        let plane = Plane::new(0, Point3::new(1.0, 1.0, 1.0), Vec3::z(), 2.0);
+        let above = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::x());
+        assert!(plane.intersects(&above, 0.0, f64::INFINITY).is_none());
+        let inside = Ray::new(Point3::origin(), Vec3::x());
+        assert!(plane.intersects(&inside, 0.0, f64::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use nalgebra::{Point2, Point3};
+
+use crate::aabb::AABB;
+use crate::geometric_object::{project_to_plane, scale_direction, scale_point, Geometry};
+use crate::model::Vec3;
+use crate::ray::{error_bound, HitRecord, Ray};
+use crate::sampler::get_square_sampler;
+
+// parallelogram spanned by the edges a and b from the corner p0
+pub struct Rectangle {
+    p0: Point3<f64>,
+    a: Vec3,
+    b: Vec3,
+    normal: Vec3,
+    material_id: usize,
+}
+
+impl Rectangle {
+    pub fn new(material_id: usize, p0: Point3<f64>, a: Vec3, b: Vec3, scale: f64) -> Rectangle {
+        let mut rectangle = Rectangle {
+            p0,
+            a,
+            b,
+            normal: a.cross(&b).normalize(),
+            material_id,
+        };
+        rectangle.scale(scale);
+        rectangle
+    }
+
+    // the corner p0 and the edges a and b
+    pub fn edges(&self) -> (Point3<f64>, Vec3, Vec3) {
+        (self.p0, self.a, self.b)
+    }
+}
+
+impl Geometry for Rectangle {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.p0 - ray.origin).dot(&self.normal) / ray.dir.dot(&self.normal);
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+        let (hit_point, error) = project_to_plane(ray.get_point(t), &self.p0, &self.normal);
+        // coordinates along the edges, which need not be perpendicular
+        let d = hit_point - self.p0;
+        let area = self.a.cross(&self.b).dot(&self.normal);
+        let u = d.cross(&self.b).dot(&self.normal) / area;
+        let v = self.a.cross(&d).dot(&self.normal) / area;
+        if !((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)) {
+            return None;
+        }
+        Some(HitRecord {
+            dist: t,
+            hit_point,
+            normal: self.normal,
+            geometric_normal: self.normal,
+            error,
+            uv: Point2::new(u, v),
+            vertex_color: None,
+            material_id: self.material_id,
+        })
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.p0 = scale_point(self.p0, l);
+        self.a = scale_direction(self.a) * (2.0 / l);
+        self.b = scale_direction(self.b) * (2.0 / l);
+        self.normal = scale_direction(self.normal);
+    }
+
+    fn normal(&self, _p: &Point3<f64>) -> Vec3 {
+        self.normal
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.p0 + (self.a + self.b) / 2.0
+    }
+
+    // padded by the rounding of the corners
+    fn get_min_point(&self) -> Point3<f64> {
+        let corners = [
+            self.p0 + self.a,
+            self.p0 + self.b,
+            self.p0 + self.a + self.b,
+        ];
+        let min = corners.iter().fold(self.p0, |a, b| a.inf(b));
+        min - error_bound(&min, 2)
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        let corners = [
+            self.p0 + self.a,
+            self.p0 + self.b,
+            self.p0 + self.a + self.b,
+        ];
+        let max = corners.iter().fold(self.p0, |a, b| a.sup(b));
+        max + error_bound(&max, 2)
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        get_square_sampler(sample_points_sqrt)
+            .map(|p| self.p0 + self.a * p.x + self.b * p.y)
+            .collect()
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    fn area(&self) -> f64 {
+        self.a.cross(&self.b).norm()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skewed_parallelogram_is_hit_by_its_edge_coordinates() {
+        let rectangle = Rectangle::new(
+            0,
+            Point3::origin(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            2.0,
+        );
+        let (p0, a, b) = rectangle.edges();
+        let normal = rectangle.normal(&p0);
+        for (u, v, inside) in [(0.2, 0.9, true), (0.9, 0.2, true), (-0.1, 0.9, false)] {
+            let target = p0 + a * u + b * v;
+            let ray = Ray::new(target + normal, -normal);
+            let hit = rectangle.intersects(&ray, 0.0, f64::INFINITY);
+            assert_eq!(hit.is_some(), inside);
+            if let Some(hit) = hit {
+                assert!((hit.uv - Point2::new(u, v)).norm() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use nalgebra::{Point2, Point3, Vector2};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{scale_direction, scale_point, Geometry};
+use crate::model::Vec3;
+use crate::ray::{error_bound, HitRecord, Ray};
+
+// Signed distance expressions in scene units, after
+// https://iquilezles.org/articles/distfunctions/
+pub enum Sdf {
+    Sphere(f64),
+    Cuboid(Vec3),    // half extents
+    Torus(f64, f64), // ring and tube radius around the y axis
+    Capsule(Point3<f64>, Point3<f64>, f64),
+    Translate(Vec3, Box<Sdf>),
+    Union(Box<Sdf>, Box<Sdf>),
+    SmoothUnion(Box<Sdf>, Box<Sdf>, f64),
+    Twist(f64, Box<Sdf>),   // radians per unit along y
+    Repeat(Vec3, Box<Sdf>), // period per axis, 0 leaves the axis alone
+}
+
+impl Sdf {
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        Sdf::Translate(offset, Box::new(self))
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    // k is the distance over which the two shapes blend
+    pub fn smooth_union(self, other: Sdf, k: f64) -> Sdf {
+        Sdf::SmoothUnion(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn twist(self, k: f64) -> Sdf {
+        Sdf::Twist(k, Box::new(self))
+    }
+
+    pub fn repeat(self, period: Vec3) -> Sdf {
+        Sdf::Repeat(period, Box::new(self))
+    }
+
+    pub fn distance(&self, p: &Point3<f64>) -> f64 {
+        match self {
+            Sdf::Sphere(r) => p.coords.norm() - r,
+            Sdf::Cuboid(b) => {
+                let q = p.coords.abs() - b;
+                q.sup(&Vec3::zeros()).norm() + q.max().min(0.0)
+            }
+            Sdf::Torus(a, b) => Vector2::new(Vector2::new(p.x, p.z).norm() - a, p.y).norm() - b,
+            Sdf::Capsule(a, b, r) => {
+                let pa = p - a;
+                let ba = b - a;
+                let h = (pa.dot(&ba) / ba.dot(&ba)).clamp(0.0, 1.0);
+                (pa - ba * h).norm() - r
+            }
+            Sdf::Translate(offset, sdf) => sdf.distance(&(p - offset)),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 + (d1 - d2) * h - k * h * (1.0 - h)
+            }
+            Sdf::Twist(k, sdf) => {
+                let (s, c) = (k * p.y).sin_cos();
+                sdf.distance(&Point3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z))
+            }
+            Sdf::Repeat(period, sdf) => {
+                let wrap = |x: f64, c: f64| if c > 0.0 { x - c * (x / c).round() } else { x };
+                sdf.distance(&Point3::from(p.coords.zip_map(period, wrap)))
+            }
+        }
+    }
+}
+
+const MAX_STEPS: usize = 512;
+// where the march stops, scenes are normalised to [-1, 1] so it is a fixed
+// fraction of the scene rather than a bound on rounding
+const EPSILON: f64 = 1e-5;
+
+// An Sdf placed at a center and clipped to a box of the given half extents,
+// intersected by sphere tracing. Twists stretch distances, lower the step for
+// them so the tracing doesn't overshoot
+pub struct SdfObject {
+    sdf: Sdf,
+    center: Point3<f64>,
+    bounds: Vec3,
+    factor: f64, // scene to world units
+    step: f64,
+    material_id: usize,
+}
+
+impl SdfObject {
+    pub fn new(
+        material_id: usize,
+        sdf: Sdf,
+        center: Point3<f64>,
+        bounds: Vec3,
+        scale: f64,
+    ) -> SdfObject {
+        let mut object = SdfObject {
+            sdf,
+            center,
+            bounds,
+            factor: 1.0,
+            step: 1.0,
+            material_id,
+        };
+        object.scale(scale);
+        object
+    }
+
+    pub fn with_step(mut self, step: f64) -> SdfObject {
+        self.step = step;
+        self
+    }
+
+    fn distance(&self, p: &Point3<f64>) -> f64 {
+        let local = scale_direction(p - self.center) / self.factor;
+        self.sdf.distance(&Point3::from(local)) * self.factor
+    }
+}
+
+impl Geometry for SdfObject {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.get_bounding_box().hit_range(ray, t_min, t_max)?;
+        let speed = ray.dir.norm();
+        let mut t = start;
+        // rays leaving the surface march until they are clear of it
+        let mut leaving = self.distance(&ray.get_point(t)).abs() < EPSILON;
+        for _ in 0..MAX_STEPS {
+            if t > end {
+                return None;
+            }
+            let d = self.distance(&ray.get_point(t)).abs() / speed;
+            if leaving {
+                leaving = d < EPSILON;
+            } else if d < EPSILON {
+                let hit_point = ray.get_point(t);
+                let normal = self.normal(&hit_point);
+                let n = scale_direction(normal);
+                return Some(HitRecord {
+                    dist: t,
+                    hit_point,
+                    normal,
+                    geometric_normal: normal,
+                    // the march stops within EPSILON of the surface
+                    error: Vec3::repeat(EPSILON * speed) + error_bound(&hit_point, 7),
+                    uv: Point2::new(0.5 + n.z.atan2(n.x) / (2.0 * PI), n.y.acos() / PI),
+                    vertex_color: None,
+                    material_id: self.material_id,
+                });
+            }
+            t += (d * self.step).max(EPSILON);
+        }
+        None
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.center = scale_point(self.center, l);
+        self.factor = 2.0 / l;
+        self.bounds *= self.factor;
+    }
+
+    // central differences
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let h = 1e-4;
+        let d = |axis: usize| {
+            let mut offset = Vec3::zeros();
+            offset[axis] = h;
+            self.distance(&(p + offset)) - self.distance(&(p - offset))
+        };
+        Vec3::new(d(0), d(1), d(2)).normalize()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.center
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.center - self.bounds
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.center + self.bounds
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        AABB::new(self.get_min_point(), self.get_max_point())
+    }
+
+    fn get_samples(&self, _sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        vec![]
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_of_the_primitives() {
+        let p = Point3::new(0.0, 2.0, 0.0);
+        assert!((Sdf::Sphere(0.5).distance(&p) - 1.5).abs() < 1e-12);
+        assert!((Sdf::Cuboid(Vec3::repeat(1.0)).distance(&p) - 1.0).abs() < 1e-12);
+        assert!((Sdf::Cuboid(Vec3::repeat(1.0)).distance(&Point3::origin()) + 1.0).abs() < 1e-12);
+        assert!((Sdf::Torus(1.0, 0.25).distance(&Point3::new(1.0, 0.0, 0.0)) + 0.25).abs() < 1e-12);
+        let capsule = Sdf::Capsule(Point3::origin(), Point3::new(0.0, 1.0, 0.0), 0.5);
+        assert!((capsule.distance(&p) - 0.5).abs() < 1e-12);
+        let moved = Sdf::Sphere(0.5).translate(Vec3::new(0.0, 2.0, 0.0));
+        assert!((moved.distance(&p) + 0.5).abs() < 1e-12);
+        let both = Sdf::Sphere(0.5).union(Sdf::Sphere(0.5).translate(Vec3::new(0.0, 3.0, 0.0)));
+        assert!((both.distance(&p) - 0.5).abs() < 1e-12);
+        // blending only ever adds material
+        let blend = Sdf::Sphere(0.5).smooth_union(Sdf::Sphere(0.5).translate(Vec3::y()), 0.5);
+        let mid = Point3::new(0.6, 0.5, 0.0);
+        assert!(blend.distance(&mid) < Sdf::Sphere(0.5).distance(&mid));
+        let tiled = Sdf::Sphere(0.5).repeat(Vec3::new(0.0, 4.0, 0.0));
+        assert!((tiled.distance(&Point3::new(0.0, 8.0, 0.0)) + 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_surface() {
+        // a sphere of radius 0.5 at the origin once scaled, clipped to its box
+        let object = SdfObject::new(
+            0,
+            Sdf::Sphere(0.25),
+            Point3::new(0.5, 0.5, 0.5),
+            Vec3::repeat(0.3),
+            1.0,
+        );
+        let ray = Ray::new(Point3::new(0.0, 0.0, -2.0), Vec3::z());
+        let hit = object.intersects(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 1.5).abs() < 1e-4);
+        assert!((hit.normal + Vec3::z()).norm() < 1e-3);
+        // from the surface the ray leaves and finds the far side
+        let hit = object.intersects(&ray, hit.dist, f64::INFINITY).unwrap();
+        assert!((hit.dist - 2.5).abs() < 1e-4);
+        let beside = Ray::new(Point3::new(0.55, 0.0, -2.0), Vec3::z());
+        assert!(object.intersects(&beside, 0.0, f64::INFINITY).is_none());
+    }
+}
@@ -1,10 +1,11 @@
-use nalgebra::Point3;
-use std::ops::{MulAssign, SubAssign};
+use nalgebra::{Point2, Point3};
+use std::f64::consts::PI;
 
 use crate::aabb::AABB;
-use crate::geometric_object::Geometry;
+use crate::error::SceneError;
+use crate::geometric_object::{scale_point, Crossings, Geometry};
 use crate::model::Vec3;
-use crate::ray::{HitRecord, Ray};
+use crate::ray::{gamma, HitRecord, Ray};
 
 pub struct Sphere {
     radius: f64,
@@ -22,29 +23,19 @@ impl Sphere {
         sphere.scale(scale);
         sphere
     }
-}
 
-impl Geometry for Sphere {
-    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let center = self.center;
-        let radius = self.radius;
-        let start = ray.origin;
-        let dx = ray.dir.x;
-        let dy = ray.dir.y;
-        let dz = ray.dir.z;
-
-        let a = dx * dx + dy * dy + dz * dz;
-        let b = 2.0 * dx * (start.x - center.x)
-            + 2.0 * dy * (start.y - center.y)
-            + 2.0 * dz * (start.z - center.z);
-        let c = center.x * center.x
-            + center.y * center.y
-            + center.z * center.z
-            + start.x * start.x
-            + start.y * start.y
-            + start.z * start.z
-            - 2.0 * (center.x * start.x + center.y * start.y + center.z * start.z)
-            - radius * radius;
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // The origin is taken relative to the center before squaring, expanding
+    // |o - c|^2 instead would cancel away the offset of rays spawned from the
+    // surface far from the scene origin
+    fn roots(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let oc = ray.origin - self.center;
+        let a = ray.dir.norm_squared();
+        let b = 2.0 * ray.dir.dot(&oc);
+        let c = oc.norm_squared() - self.radius * self.radius;
 
         let disc = b * b - 4.0 * a * c;
 
@@ -52,27 +43,66 @@ impl Geometry for Sphere {
             return None;
         }
 
-        let t = (-b - disc.sqrt()) / (2.0 * a);
-        if t < 0.0 {
-            return None;
-        }
-        if t < t_min || t > t_max {
-            return None;
-        }
+        Some((
+            (-b - disc.sqrt()) / (2.0 * a),
+            (-b + disc.sqrt()) / (2.0 * a),
+        ))
+    }
 
-        let hit_point = ray.get_point(t);
-        Some(HitRecord {
+    // the hit point is moved back onto the sphere, leaving only the rounding
+    // error of that projection
+    fn hit_record(&self, ray: &Ray, t: f64) -> HitRecord {
+        let local = ray.get_point(t) - self.center;
+        let local = local * (self.radius / local.norm());
+        let hit_point = self.center + local;
+        let normal = self.normal(&hit_point);
+        HitRecord {
             dist: t,
             hit_point,
-            normal: self.normal(&hit_point),
+            normal,
+            geometric_normal: normal,
+            error: local.abs() * gamma(5) + hit_point.coords.abs() * gamma(1),
+            uv: Point2::new(
+                0.5 + normal.z.atan2(normal.x) / (2.0 * PI),
+                normal.y.acos() / PI,
+            ),
+            vertex_color: None,
             material_id: self.material_id,
-        })
+        }
+    }
+}
+
+impl Geometry for Sphere {
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // rays leaving the inside only cross the far side
+        let (near, far) = self.roots(ray)?;
+        let t = if near > t_min { near } else { far };
+        if t <= t_min || t > t_max {
+            return None;
+        }
+        Some(self.hit_record(ray, t))
+    }
+
+    // both roots, intersects only reports the near one
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Result<Crossings, SceneError> {
+        Ok(self.roots(ray).map_or(
+            Crossings {
+                starts_inside: false,
+                hits: vec![],
+            },
+            |(near, far)| Crossings {
+                starts_inside: near <= t_min && t_min < far,
+                hits: [near, far]
+                    .iter()
+                    .filter(|t| **t > t_min && **t <= t_max)
+                    .map(|t| self.hit_record(ray, *t))
+                    .collect(),
+            },
+        ))
     }
 
     fn scale(&mut self, l: f64) {
-        self.center.mul_assign(2.0 / l);
-        self.center.sub_assign(Vec3::repeat(1.0));
-        self.center.mul_assign(-1.0);
+        self.center = scale_point(self.center, l);
         self.radius = (self.radius * 2.0) / l;
     }
 
@@ -103,4 +133,8 @@ impl Geometry for Sphere {
     fn get_material_id(&self) -> usize {
         self.material_id
     }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
 }
@@ -0,0 +1,254 @@
+use nalgebra::{Point2, Point3};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::geometric_object::{
+    nearer, nearest_root, surface_distance, sweep_angle, Frame, Geometry, LocalHit,
+};
+use crate::model::Vec3;
+use crate::ray::{HitRecord, Ray};
+use crate::solver::solve_quartic;
+
+// Torus around the axis through its center, a is the radius of the ring and
+// b the radius of the tube. Caps close the ends of a partial sweep
+pub struct Torus {
+    frame: Frame,
+    a: f64,
+    b: f64,
+    phi_max: f64,
+    capped: bool,
+    material_id: usize,
+}
+
+impl Torus {
+    pub fn new(
+        material_id: usize,
+        center: Point3<f64>,
+        axis: Vec3,
+        a: f64,
+        b: f64,
+        scale: f64,
+    ) -> Torus {
+        let mut torus = Torus {
+            frame: Frame::new(center, axis),
+            a,
+            b,
+            phi_max: 2.0 * PI,
+            capped: false,
+            material_id,
+        };
+        torus.scale(scale);
+        torus
+    }
+
+    // only the part swept from 0 to phi_max radians around the axis
+    pub fn with_sweep(mut self, phi_max: f64) -> Torus {
+        self.phi_max = phi_max;
+        self
+    }
+
+    pub fn with_caps(mut self) -> Torus {
+        self.capped = true;
+        self
+    }
+
+    fn local_box(&self) -> (Point3<f64>, Point3<f64>) {
+        let r = self.a + self.b;
+        (Point3::new(-r, -self.b, -r), Point3::new(r, self.b, r))
+    }
+
+    fn local_normal(&self, p: &Point3<f64>) -> Vec3 {
+        let s = p.coords.norm_squared() + self.a * self.a - self.b * self.b;
+        let a2 = 2.0 * self.a * self.a;
+        Vec3::new(p.x * (s - a2), p.y * s, p.z * (s - a2))
+    }
+
+    fn side_distance(&self, p: &Point3<f64>) -> f64 {
+        let (a2, b2) = (self.a * self.a, self.b * self.b);
+        let norm2 = p.coords.norm_squared();
+        let ring = 4.0 * a2 * (p.x * p.x + p.z * p.z);
+        let s = norm2 + a2 - b2;
+        let terms = (norm2 + a2 + b2) * (norm2 + a2 + b2) + ring;
+        surface_distance(s * s - ring, terms, 4.0 * self.local_normal(p).norm(), 8)
+    }
+
+    // disk closing the tube where the sweep at angle phi ends, facing away from the torus
+    fn cap(&self, local: &Ray, phi: f64, side: f64, t_min: f64, t_max: f64) -> Option<LocalHit> {
+        let normal = Vec3::new(-phi.sin(), 0.0, phi.cos()) * side;
+        let center = Point3::new(self.a * phi.cos(), 0.0, self.a * phi.sin());
+        let d = local.dir.dot(&normal);
+        if d == 0.0 {
+            return None;
+        }
+        let t = (center - local.origin).dot(&normal) / d;
+        nearest_root(local, vec![t], t_min, t_max, |p| {
+            (p - center).norm() <= self.b
+        })
+        .map(|(t, p)| {
+            (
+                t,
+                normal,
+                Point2::new(phi / self.phi_max, (p - center).norm() / self.b),
+                (p - center).dot(&normal).abs(),
+            )
+        })
+    }
+}
+
+impl Geometry for Torus {
+    // (x^2 + y^2 + z^2 + a^2 - b^2)^2 = 4 a^2 (x^2 + z^2)
+    fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.get_bounding_box().intersects(ray, t_min, t_max) {
+            return None;
+        }
+        let local = self.frame.to_local(ray);
+        let (o, d) = (local.origin, local.dir);
+        let four_a2 = 4.0 * self.a * self.a;
+        let dd = d.norm_squared();
+        let e = o.coords.norm_squared() - self.a * self.a - self.b * self.b;
+        let f = o.coords.dot(&d);
+        let roots = solve_quartic([
+            e * e - four_a2 * (self.b * self.b - o.y * o.y),
+            4.0 * f * e + 2.0 * four_a2 * o.y * d.y,
+            2.0 * dd * e + 4.0 * f * f + four_a2 * d.y * d.y,
+            4.0 * dd * f,
+            dd * dd,
+        ]);
+        let side = nearest_root(&local, roots, t_min, t_max, |p| {
+            sweep_angle(p) <= self.phi_max
+        })
+        .map(|(t, p)| {
+            let ring = (p.x * p.x + p.z * p.z).sqrt() - self.a;
+            let theta = sweep_angle(&Point3::new(ring, 0.0, p.y));
+            let uv = Point2::new(sweep_angle(&p) / self.phi_max, theta / (2.0 * PI));
+            (t, self.local_normal(&p), uv, self.side_distance(&p))
+        });
+
+        let caps = if self.capped && self.phi_max < 2.0 * PI {
+            nearer(
+                self.cap(&local, 0.0, -1.0, t_min, t_max),
+                self.cap(&local, self.phi_max, 1.0, t_min, t_max),
+            )
+        } else {
+            None
+        };
+
+        Some(
+            self.frame
+                .hit_record(ray, nearer(side, caps)?, self.material_id),
+        )
+    }
+
+    fn scale(&mut self, l: f64) {
+        self.frame.scale(l);
+        self.a = (self.a * 2.0) / l;
+        self.b = (self.b * 2.0) / l;
+    }
+
+    fn normal(&self, p: &Point3<f64>) -> Vec3 {
+        let p = self.frame.to_local_point(p);
+        self.frame.to_world(&self.local_normal(&p)).normalize()
+    }
+
+    fn get_center(&self) -> Point3<f64> {
+        self.frame.to_world_point(&Point3::origin())
+    }
+
+    fn get_min_point(&self) -> Point3<f64> {
+        self.get_bounding_box().min
+    }
+
+    fn get_max_point(&self) -> Point3<f64> {
+        self.get_bounding_box().max
+    }
+
+    fn get_bounding_box(&self) -> AABB {
+        let (min, max) = self.local_box();
+        self.frame.bounding_box(min, max)
+    }
+
+    // the tube only, its area grows with the distance from the axis
+    fn get_samples(&self, sample_points_sqrt: usize) -> Vec<Point3<f64>> {
+        self.frame
+            .sample_surface(sample_points_sqrt, self.a + self.b, |s| {
+                let phi = s.x * self.phi_max;
+                let theta = s.y * 2.0 * PI;
+                let r = self.a + self.b * theta.cos();
+                let p = Point3::new(r * phi.cos(), self.b * theta.sin(), r * phi.sin());
+                (p, r)
+            })
+    }
+
+    fn get_material_id(&self) -> usize {
+        self.material_id
+    }
+
+    // caps close the tube where a partial sweep ends
+    fn is_closed(&self) -> bool {
+        self.phi_max >= 2.0 * PI || self.capped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ring of radius 0.5 and tube of radius 0.2 about the origin once scaled
+    fn torus() -> Torus {
+        Torus::new(0, Point3::new(1.0, 1.0, 1.0), Vec3::y(), 0.5, 0.2, 2.0)
+    }
+
+    #[test]
+    fn ray_through_the_hole_crosses_four_times() {
+        let ray = Ray::new(Point3::new(-2.0, 0.0, 0.0), Vec3::x());
+        let crossings = torus().crossings(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(!crossings.starts_inside);
+        let hits = crossings.hits;
+        assert_eq!(hits.len(), 4);
+        for (hit, t) in hits.iter().zip([1.3, 1.7, 2.3, 2.7]) {
+            assert!((hit.dist - t).abs() < 1e-9, "{} {}", hit.dist, t);
+        }
+        // entering and leaving the tube in turn
+        for (i, hit) in hits.iter().enumerate() {
+            assert_eq!(hit.normal.dot(&ray.dir) < 0.0, i % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn ray_grazing_past_the_tube_misses() {
+        let ray = Ray::new(Point3::new(-2.0, 0.21, 0.0), Vec3::x());
+        assert!(torus().intersects(&ray, 0.0, f64::INFINITY).is_none());
+        let ray = Ray::new(Point3::new(-2.0, 0.19, 0.0), Vec3::x());
+        assert_eq!(
+            torus()
+                .crossings(&ray, 0.0, f64::INFINITY)
+                .unwrap()
+                .hits
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn caps_close_a_partial_sweep() {
+        let half = torus().with_sweep(PI);
+        assert!(!half.is_closed());
+        assert!(torus().is_closed());
+        let capped = torus().with_sweep(PI).with_caps();
+        assert!(capped.is_closed());
+        // across the cut through the middle of the tube, local z = 0 is the cap
+        let frame = &capped.frame;
+        let ray = Ray::new(
+            frame.to_world_point(&Point3::new(0.5, 0.0, -2.0)),
+            frame.to_world(&Vec3::z()),
+        );
+        let open = half.crossings(&ray, 0.0, f64::INFINITY).unwrap().hits;
+        assert_eq!(open.len(), 1);
+        let hits = capped.crossings(&ray, 0.0, f64::INFINITY).unwrap().hits;
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().any(|hit| (hit.dist - 2.0).abs() < 1e-9));
+        assert!(hits
+            .iter()
+            .any(|hit| (hit.dist - open[0].dist).abs() < 1e-9));
+    }
+}
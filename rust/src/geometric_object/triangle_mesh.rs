@@ -0,0 +1,248 @@
+use nalgebra::{Point2, Point3};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::color::Color;
+use crate::geometric_object::{Geometry, Triangle};
+use crate::model::Vec3;
+use crate::ray::{gamma, HitRecord, Ray};
+
+// Vertex attributes are stored once per mesh, triangles refer to them by index
+pub struct TriangleMesh {
+    pub positions: Vec<Point3<f64>>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Point2<f64>>>,
+    pub colors: Option<Vec<Color>>,
+    pub faces: Vec<[u32; 3]>,
+    pub material_id: usize,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        material_id: usize,
+        positions: Vec<Point3<f64>>,
+        faces: Vec<[u32; 3]>,
+        scale: f64,
+    ) -> TriangleMesh {
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: None,
+            uvs: None,
+            colors: None,
+            faces,
+            material_id,
+        };
+        mesh.scale(scale);
+        mesh
+    }
+
+    // vertex normals are given before scaling, same as the positions
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> TriangleMesh {
+        self.normals = Some(
+            normals
+                .into_iter()
+                .map(|n| Vec3::new(-n.x, -n.y, n.z).normalize())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<Point2<f64>>) -> TriangleMesh {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Color>) -> TriangleMesh {
+        self.colors = Some(colors);
+        self
+    }
+
+    // same normalisation as Geometry::scale
+    fn scale(&mut self, l: f64) {
+        for p in &mut self.positions {
+            *p *= 2.0 / l;
+            *p -= Vec3::new(1.0, 1.0, 1.0);
+            p.x = -p.x;
+            p.y = -p.y;
+        }
+    }
+
+    // one triangle per face, e.g. for a BvhNode shared by several instances
+    pub fn triangles(self: &Arc<Self>) -> Vec<Arc<dyn Geometry + Send + Sync>> {
+        (0..self.faces.len())
+            .map(|f| Arc::new(Triangle::new(self.clone(), f)) as Arc<dyn Geometry + Send + Sync>)
+            .collect()
+    }
+
+    pub fn vertices(&self, face: usize) -> [Point3<f64>; 3] {
+        self.faces[face].map(|i| self.positions[i as usize])
+    }
+
+    // per-vertex attributes of a face
+    fn attributes<T: Copy>(&self, face: usize, values: &[T]) -> [T; 3] {
+        self.faces[face].map(|i| values[i as usize])
+    }
+
+    pub fn face_normal(&self, face: usize) -> Vec3 {
+        let [x, y, z] = self.vertices(face);
+        (z - x).cross(&(y - x)).normalize()
+    }
+
+    pub fn face_bounds(&self, face: usize) -> AABB {
+        let [x, y, z] = self.vertices(face);
+        AABB::new(x.inf(&y).inf(&z), x.sup(&y).sup(&z))
+    }
+
+    pub fn face_area(&self, face: usize) -> f64 {
+        let [x, y, z] = self.vertices(face);
+        (y - x).cross(&(z - x)).norm() / 2.0
+    }
+
+    pub fn intersect_face(
+        &self,
+        face: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let [x, y, z] = self.vertices(face);
+        let e1 = y - x;
+        let e2 = z - x;
+
+        let h = ray.dir.cross(&e2);
+        let a = e1.dot(&h);
+        if a == 0.0 {
+            return None;
+        }
+
+        let f = a.recip();
+        let s = ray.origin - x;
+        let u = f * s.dot(&h);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&e1);
+        let v = f * ray.dir.dot(&q);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = f * e2.dot(&q);
+        if t <= t_min || t > t_max {
+            return None;
+        }
+
+        // from the barycentrics the error only depends on the vertices
+        let hit_point = Point3::from(interpolate(&[x.coords, y.coords, z.coords], u, v));
+        let error = interpolate(&[x.coords.abs(), y.coords.abs(), z.coords.abs()], u, v) * gamma(7);
+        let geometric_normal = self.face_normal(face);
+        let normal = self.normals.as_ref().map_or(geometric_normal, |normals| {
+            interpolate(&self.attributes(face, normals), u, v).normalize()
+        });
+        let uv = self.uvs.as_ref().map_or(Point2::new(u, v), |uvs| {
+            let coords = self.attributes(face, uvs).map(|uv| uv.coords);
+            Point2::from(interpolate(&coords, u, v))
+        });
+        let vertex_color = self
+            .colors
+            .as_ref()
+            .map(|colors| interpolate(&self.attributes(face, colors), u, v));
+        Some(HitRecord {
+            dist: t,
+            hit_point,
+            normal,
+            geometric_normal,
+            error,
+            uv,
+            vertex_color,
+            material_id: self.material_id,
+        })
+    }
+
+    pub fn find_face_out_of_range(&self) -> Option<usize> {
+        let len = self.positions.len();
+        self.faces
+            .iter()
+            .position(|face| face.iter().any(|i| *i as usize >= len))
+    }
+
+    // drops the faces without a finite area, returning how many there were
+    pub fn remove_faces_without_area(&mut self) -> usize {
+        let len = self.faces.len();
+        let positions = &self.positions;
+        self.faces.retain(|face| {
+            let [x, y, z] = face.map(|i| positions[i as usize]);
+            let area = (y - x).cross(&(z - x)).norm();
+            area > 0.0 && area.is_finite()
+        });
+        len - self.faces.len()
+    }
+}
+
+// interpolates per-vertex values with the barycentric coordinates (u, v) of the hit
+fn interpolate<T>(values: &[T; 3], u: f64, v: f64) -> T
+where
+    T: Copy + std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T>,
+{
+    values[0] * (1.0 - u - v) + values[1] * u + values[2] * v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit right triangle at z = 0 once scaled, with its right angle at the origin
+    fn right_triangle() -> TriangleMesh {
+        let positions = vec![
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(0.0, 1.0, 1.0),
+            Point3::new(1.0, 0.0, 1.0),
+        ];
+        TriangleMesh::new(3, positions, vec![[0, 1, 2]], 2.0)
+    }
+
+    #[test]
+    fn hit_interpolates_the_vertex_attributes() {
+        let mesh = right_triangle()
+            .with_normals(vec![Vec3::z(); 3])
+            .with_uvs(vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(1.0, 0.0),
+                Point2::new(0.0, 1.0),
+            ])
+            .with_colors(vec![Color::x(), Color::y(), Color::z()]);
+        let ray = Ray::new(Point3::new(0.25, 0.5, -1.0), Vec3::z());
+        let hit = mesh.intersect_face(0, &ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.dist - 1.0).abs() < 1e-12);
+        assert!((hit.uv - Point2::new(0.25, 0.5)).norm() < 1e-12);
+        assert!((hit.vertex_color.unwrap() - Color::new(0.25, 0.25, 0.5)).norm() < 1e-12);
+        assert!((hit.normal - Vec3::z()).norm() < 1e-12);
+        assert_eq!(hit.material_id, 3);
+        assert!(mesh.intersect_face(0, &ray, 0.0, 0.5).is_none());
+        let outside = Ray::new(Point3::new(0.75, 0.5, -1.0), Vec3::z());
+        assert!(mesh
+            .intersect_face(0, &outside, 0.0, f64::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn triangles_share_the_mesh() {
+        let mesh = Arc::new(right_triangle());
+        let triangles = mesh.triangles();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(Arc::strong_count(&mesh), 2);
+        assert!((mesh.face_area(0) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn faces_are_checked_against_the_positions() {
+        let mut mesh = right_triangle();
+        mesh.faces.extend([[0, 1, 1], [0, 1, 2]]);
+        assert_eq!(mesh.find_face_out_of_range(), None);
+        assert_eq!(mesh.remove_faces_without_area(), 1);
+        assert_eq!(mesh.faces.len(), 2);
+        mesh.faces.push([0, 1, 3]);
+        assert_eq!(mesh.find_face_out_of_range(), Some(2));
+    }
+}
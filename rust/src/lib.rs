@@ -0,0 +1,27 @@
+#![allow(
+    clippy::cast_lossless,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::many_single_char_names,
+    clippy::module_name_repetitions,
+    clippy::similar_names,
+    clippy::unused_self,
+    clippy::upper_case_acronyms
+)]
+
+pub mod aabb;
+pub mod asset;
+pub mod brdf;
+pub mod btdf;
+pub mod camera;
+pub mod color;
+pub mod error;
+pub mod geometric_object;
+pub mod light;
+pub mod material;
+pub mod model;
+pub mod ray;
+pub mod sampler;
+pub mod solver;
+pub mod view_plane;
+pub mod world;
@@ -1,12 +1,15 @@
-use std::f64::INFINITY;
+use rand::{thread_rng, Rng};
+use std::f64::consts::{FRAC_1_PI, PI};
 
-use crate::color::Color;
-use crate::light::Light;
+use crate::color::{luminance, Color};
+use crate::light::{Light, LightSample};
 use crate::model::Vec3;
 use crate::ray::RayHit;
 
-use crate::sampler::get_hemisphere_sampler;
+use crate::sampler::hemisphere_direction;
 
+// Uniform light from every unoccluded direction above the surface, ls is the
+// irradiance of the open hemisphere
 pub struct AmbientOcculuder {
     pub ls: f64,
     pub cl: Color,
@@ -23,22 +26,41 @@ impl AmbientOcculuder {
 }
 
 impl Light for AmbientOcculuder {
-    fn get_direction(&self, hit: &RayHit) -> Vec3 {
+    // cosine weighted directions
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
         let (u, v, w) = self.uvw(hit);
-        u + v + w
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| {
+                let sp = hemisphere_direction(rng.gen(), rng.gen());
+                let wi = (u * sp.x + v * sp.y + w * sp.z).normalize();
+                let radiance = self.cl * (self.ls * FRAC_1_PI);
+                LightSample::unless_shadowed(hit, wi, f64::INFINITY, radiance, sp.z * FRAC_1_PI)
+            })
+            .collect()
     }
 
-    fn radiance(&self, _hit: &RayHit) -> Color {
-        self.cl * self.ls
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
     }
 
-    fn shadow_amount(&self, hit: &RayHit) -> f64 {
-        let (u, v, w) = self.uvw(hit);
-        let sample_points = (self.sample_points_sqrt * self.sample_points_sqrt) as f64;
-        let total = get_hemisphere_sampler(self.sample_points_sqrt)
-            .map(|sp| (u * sp.x + v * sp.y + w * sp.z).normalize())
-            .filter(|dir| !hit.world.is_in_shadow(&hit.hit_point, dir, INFINITY))
-            .count() as f64;
-        total / sample_points
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let cos_theta = hit.normal.dot(wi);
+        if cos_theta <= 0.0 {
+            return Some(LightSample::missed(*wi));
+        }
+        let radiance = self.cl * (self.ls * FRAC_1_PI);
+        Some(LightSample::unless_shadowed(
+            hit,
+            *wi,
+            f64::INFINITY,
+            radiance,
+            cos_theta * FRAC_1_PI,
+        ))
+    }
+
+    // ls is an irradiance, like that of a directional light
+    fn power(&self, scene_radius: f64) -> f64 {
+        PI * scene_radius * scene_radius * self.ls * luminance(&self.cl)
     }
 }
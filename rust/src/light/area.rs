@@ -1,16 +1,26 @@
-use nalgebra::{center, distance, Point3};
+use nalgebra::Point3;
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use crate::color::Color;
-use crate::geometric_object::Geometry;
-use crate::light::Light;
+use crate::aabb::AABB;
+
+use crate::color::{luminance, Color};
+use crate::error::SceneError;
+use crate::geometric_object::{BvhNode, Geometry};
+use crate::light::{Light, LightBounds, LightSample};
 use crate::material::Emissive;
 use crate::model::Vec3;
 use crate::ray::RayHit;
+use crate::sampler::Distribution;
 
+// Emitting objects, sampled in proportion to their area with the same
+// number of samples per shading point however many objects there are. Every
+// object must have an area and give points on its surface
 pub struct AreaLight {
-    center: Point3<f64>,
     geometric_objects: Vec<Arc<dyn Geometry + Send + Sync>>,
+    bvh: BvhNode, // over the objects, to find the one a direction reaches
+    distribution: Distribution,
     sample_points_sqrt: usize,
     pub material: Emissive,
 }
@@ -19,42 +29,204 @@ impl AreaLight {
     pub fn new(
         geometric_objects: Vec<Arc<dyn Geometry + Send + Sync>>,
         material: Emissive,
-    ) -> AreaLight {
-        let center = geometric_objects
+    ) -> Result<AreaLight, SceneError> {
+        if !geometric_objects
             .iter()
-            .map(|o| o.get_center())
-            .fold(Point3::origin(), |a, b| center(&a, &b));
-        AreaLight {
-            center,
+            .all(|o| o.area() > 0.0 && !o.get_samples(1).is_empty())
+        {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: "area lights need shapes that can be sampled over their area".to_owned(),
+            });
+        }
+        let areas: Vec<f64> = geometric_objects.iter().map(|o| o.area()).collect();
+        Ok(AreaLight {
+            bvh: BvhNode::new(geometric_objects.clone(), 0, geometric_objects.len()),
+            distribution: Distribution::new(&areas),
             geometric_objects,
             sample_points_sqrt: 5,
             material,
-        }
+        })
     }
 }
 
 impl Light for AreaLight {
-    fn get_direction(&self, hit: &RayHit) -> Vec3 {
-        (self.center - hit.hit_point).normalize()
+    // An object is picked with probability A_i/A and a point on it with
+    // density 1/A_i, so points are uniform over the whole area
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        let total_area = self.distribution.total();
+        let mut rng = thread_rng();
+        (0..n)
+            .filter_map(|_| {
+                let object = &self.geometric_objects[self.distribution.sample(rng.gen())?];
+                let point_on_light = object.get_samples(1)[0];
+                let normal = object.normal(&point_on_light);
+                Some(area_sample(
+                    hit,
+                    &point_on_light,
+                    &normal,
+                    total_area,
+                    self.material.radiance(),
+                ))
+            })
+            .collect()
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
     }
 
-    fn radiance(&self, _hit: &RayHit) -> Color {
-        self.material.radiance()
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let nearest = self.bvh.intersects(&hit.spawn_ray(*wi), 0.0, f64::INFINITY);
+        Some(nearest.map_or_else(
+            || LightSample::missed(*wi),
+            |record| {
+                let area = self.distribution.total();
+                area_sample(
+                    hit,
+                    &record.hit_point,
+                    &record.geometric_normal,
+                    area,
+                    self.material.radiance(),
+                )
+            },
+        ))
     }
 
-    fn shadow_amount(&self, hit: &RayHit) -> f64 {
-        let sqrt = self.sample_points_sqrt as f64;
-        let weight = sqrt * sqrt * self.geometric_objects.len() as f64;
-        let total = self
+    // emitters shine from both faces
+    fn power(&self, _scene_radius: f64) -> f64 {
+        2.0 * PI * self.distribution.total() * luminance(&self.material.radiance())
+    }
+
+    // Normals facing away from the first object's are turned around, both
+    // faces emit, so a flat mesh keeps a narrow cone
+    fn bounds(&self) -> Option<LightBounds> {
+        let first = self.geometric_objects.first()?;
+        let reference = first.normal(&first.get_center());
+        let normals: Vec<Vec3> = self
             .geometric_objects
             .iter()
-            .flat_map(|t| t.get_samples(self.sample_points_sqrt))
-            .filter(|point_on_light| {
-                let wi = (point_on_light - hit.hit_point).normalize(); // light direction
-                let d = distance(point_on_light, &hit.hit_point);
-                !hit.world.is_in_shadow(&hit.hit_point, &wi, d)
+            .map(|o| {
+                let n = o.normal(&o.get_center());
+                if n.dot(&reference) < 0.0 {
+                    -n
+                } else {
+                    n
+                }
             })
-            .count() as f64;
-        total / weight
+            .collect();
+        let sum: Vec3 = normals.iter().sum();
+        let (w, cos_theta_o) = if sum.norm() > 0.0 {
+            let w = sum.normalize();
+            let cos_theta_o = normals.iter().map(|n| n.dot(&w)).fold(1.0, f64::min);
+            (w, cos_theta_o)
+        } else {
+            (Vec3::z(), -1.0)
+        };
+        let bounds = self
+            .geometric_objects
+            .iter()
+            .map(|o| o.get_bounding_box())
+            .reduce(|a, b| AABB::get_surrounding_aabb(&a, &b))?;
+        Some(LightBounds {
+            bounds,
+            w,
+            phi: self.power(0.0),
+            cos_theta_o,
+            cos_theta_e: 0.0,
+            two_sided: true,
+        })
+    }
+}
+
+// Sample of a point drawn uniformly from `area`, in solid angle its pdf is
+// r²/(cosθ·A). Emitters shine from both faces like Emissive::shade
+pub fn area_sample(
+    hit: &RayHit,
+    point_on_light: &Point3<f64>,
+    normal: &Vec3,
+    area: f64,
+    radiance: Color,
+) -> LightSample {
+    let d = point_on_light - hit.hit_point;
+    let r = d.norm();
+    let wi = d / r;
+    let cos_light = normal.dot(&wi).abs();
+    // edge on samples still count towards the average
+    if cos_light <= 0.0 {
+        return LightSample {
+            wi,
+            radiance: Color::zeros(),
+            pdf: 1.0,
+        };
+    }
+    LightSample::unless_shadowed(hit, wi, r, radiance, r * r / (cos_light * area))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometric_object::{Disk, Sphere};
+    use crate::light::tests::{hit, irradiance, origin_ray, world};
+
+    // disk facing the origin, in scene units of half the normalised ones
+    fn disk(x: f64, z: f64, r: f64) -> Arc<dyn Geometry + Send + Sync> {
+        Arc::new(Disk::new(
+            0,
+            Point3::new(1.0 + x, 1.0, 1.0 + z),
+            Vec3::z(),
+            r,
+            2.0,
+        ))
+    }
+
+    #[test]
+    fn shapes_without_area_samples_are_rejected() {
+        let sphere = Arc::new(Sphere::new(0, 10.0, Point3::origin(), 555.0));
+        let emissive = Emissive::new(1.0, Color::repeat(1.0));
+        assert!(AreaLight::new(vec![sphere], emissive).is_err());
+    }
+
+    #[test]
+    fn samples_are_evaluated_one_by_one() {
+        // E = π·r²/(r² + h²) for a disk of radius r at distance h
+        let (r, h) = (0.5, 1.0);
+        let world = world(vec![disk(0.0, h, r)]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let light = AreaLight::new(
+            vec![disk(0.0, h, r)],
+            Emissive::new(1.0, Color::repeat(1.0)),
+        );
+        let light = light.unwrap();
+        let samples = light.sample(&hit, 100);
+        assert!(samples.iter().any(|s| (s.wi - samples[0].wi).norm() > 1e-3));
+        let expected = PI * r * r / (r * r + h * h);
+        let estimate = irradiance(&light, &hit, 20_000);
+        assert!(
+            (estimate - expected).abs() < 0.03 * expected,
+            "{} {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn objects_are_picked_by_area() {
+        // four times the area on the left, which is +x once normalised
+        let objects = vec![disk(-1.0, 1.0, 0.6), disk(1.0, 1.0, 0.3)];
+        let world = world(objects.clone());
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let light = AreaLight::new(objects, Emissive::new(1.0, Color::repeat(1.0))).unwrap();
+        let samples = light.sample(&hit, 10_000);
+        let large = samples.iter().filter(|s| s.wi.x > 0.0).count() as f64;
+        let fraction = large / samples.len() as f64;
+        assert!((fraction - 0.8).abs() < 0.02, "{}", fraction);
+        // and each point still stands for the whole area
+        for s in samples.iter().filter(|s| s.pdf > 0.0) {
+            let sample_direction = light.sample_direction(&hit, &s.wi).unwrap();
+            assert!((sample_direction.pdf - s.pdf).abs() < 1e-6 * s.pdf);
+        }
     }
 }
@@ -0,0 +1,132 @@
+use nalgebra::{Point3, Rotation3, Unit};
+use std::f64::consts::PI;
+
+use crate::aabb::AABB;
+use crate::model::Vec3;
+
+// Where a light is, how much it emits and which way, after pbrt-v4's light
+// BVH. Emission leaves the box within cos_theta_o of w, widened by
+// cos_theta_e past those directions
+pub struct LightBounds {
+    pub bounds: AABB,
+    pub w: Vec3,
+    pub phi: f64, // power
+    pub cos_theta_o: f64,
+    pub cos_theta_e: f64,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    // bounds of both, for a node of the light BVH
+    pub fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.phi <= 0.0 {
+            return b.copy();
+        }
+        if b.phi <= 0.0 {
+            return a.copy();
+        }
+        let (w, cos_theta_o) = cone_union(&a.w, a.cos_theta_o, &b.w, b.cos_theta_o);
+        LightBounds {
+            bounds: AABB::get_surrounding_aabb(&a.bounds, &b.bounds),
+            w,
+            phi: a.phi + b.phi,
+            cos_theta_o,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+
+    pub fn centroid(&self) -> Point3<f64> {
+        nalgebra::center(&self.bounds.min, &self.bounds.max)
+    }
+
+    fn copy(&self) -> LightBounds {
+        LightBounds {
+            bounds: AABB::new(self.bounds.min, self.bounds.max),
+            ..*self
+        }
+    }
+
+    // Conservative estimate of the light reaching p on a surface facing n:
+    // the power over the squared distance, with the smallest angles between
+    // p and the directions the box can emit towards
+    pub fn importance(&self, p: &Point3<f64>, n: &Vec3) -> f64 {
+        let pc = self.centroid();
+        let diagonal = self.bounds.max - self.bounds.min;
+        let d2 = (p - pc).norm_squared().max(diagonal.norm() / 2.0);
+        let to_p = p - pc;
+        let wi = if to_p.norm() > 0.0 {
+            to_p.normalize()
+        } else {
+            Vec3::zeros()
+        };
+        let mut cos_theta_w = self.w.dot(&wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+        // the box as seen from p
+        let radius = diagonal.norm() / 2.0;
+        let cos_theta_b = if (p - pc).norm_squared() < radius * radius {
+            -1.0
+        } else {
+            safe_sqrt(1.0 - radius * radius / (p - pc).norm_squared())
+        };
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+        // the angle to p past the cone of normals and the box, clamped at 0
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+        let mut importance = self.phi * cos_theta_p / d2;
+        if n.norm_squared() > 0.0 {
+            let cos_theta_i = wi.dot(n).abs();
+            let sin_theta_i = safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+// cos(max(0, a - b)) from the sines and cosines of a and b
+fn cos_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+fn sin_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+// smallest cone around two cones of directions, as an axis and the cosine of
+// its half angle
+fn cone_union(wa: &Vec3, cos_a: f64, wb: &Vec3, cos_b: f64) -> (Vec3, f64) {
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = wa.dot(wb).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (*wa, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (*wb, cos_b);
+    }
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+    let axis = wa.cross(wb);
+    if theta_o >= PI || axis.norm_squared() == 0.0 {
+        return (*wa, -1.0);
+    }
+    let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(axis), theta_o - theta_a);
+    (rotation * wa, theta_o.cos())
+}
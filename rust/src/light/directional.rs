@@ -1,7 +1,7 @@
-use std::ops::Mul;
+use std::f64::consts::PI;
 
-use crate::color::Color;
-use crate::light::Light;
+use crate::color::{luminance, Color};
+use crate::light::{Light, LightSample};
 use crate::model::Vec3;
 use crate::ray::RayHit;
 
@@ -12,15 +12,15 @@ pub struct DirectionalLight {
 }
 
 impl Light for DirectionalLight {
-    fn get_direction(&self, _hit: &RayHit) -> Vec3 {
-        self.direction
+    fn sample(&self, _hit: &RayHit, _n: usize) -> Vec<LightSample> {
+        vec![LightSample {
+            wi: self.direction,
+            radiance: self.cl * self.ls,
+            pdf: 1.0,
+        }]
     }
 
-    fn shadow_amount(&self, _hit: &RayHit) -> f64 {
-        1.0
-    }
-
-    fn radiance(&self, _hit: &RayHit) -> Color {
-        self.cl.mul(self.ls)
+    fn power(&self, scene_radius: f64) -> f64 {
+        PI * scene_radius * scene_radius * self.ls * luminance(&self.cl)
     }
 }
@@ -0,0 +1,173 @@
+use nalgebra::Point3;
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::color::luminance;
+use crate::geometric_object::{Disk, Geometry};
+use crate::light::{area_sample, cone_direction, Light, LightBounds, LightSample};
+use crate::material::Emissive;
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+// An emitting disk. Its solid angle has no closed form sampling, directions
+// are drawn uniformly from the cone around the sphere bounding the disk and
+// those missing it carry no light. Points inside that sphere fall back to
+// drawing points uniformly over the area
+pub struct DiskLight {
+    disk: Arc<Disk>,
+    sample_points_sqrt: usize,
+    pub material: Emissive,
+}
+
+impl DiskLight {
+    pub fn new(disk: Arc<Disk>, material: Emissive) -> DiskLight {
+        DiskLight {
+            disk,
+            sample_points_sqrt: 5,
+            material,
+        }
+    }
+
+    // (axis, 1 - cos of the half angle) of the cone bounding the disk seen
+    // from p, None inside its bounding sphere
+    fn cone(&self, p: &Point3<f64>) -> Option<(Vec3, f64)> {
+        let radius = self.disk.radius();
+        let to_center = self.disk.get_center() - p;
+        let dc = to_center.norm();
+        if dc <= radius {
+            return None;
+        }
+        let sin2_max = radius * radius / (dc * dc);
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+        // 1 - cos_max without cancellation for small, distant disks
+        Some((to_center / dc, sin2_max / (1.0 + cos_max)))
+    }
+
+    // distance along wi from p to the disk
+    fn distance(&self, p: &Point3<f64>, wi: &Vec3) -> Option<f64> {
+        let normal = self.disk.normal(p);
+        let center = self.disk.get_center();
+        let t = (center - p).dot(&normal) / wi.dot(&normal);
+        let on_disk = (p + wi * t - center).norm() <= self.disk.radius();
+        if t > 0.0 && on_disk {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for DiskLight {
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        let radiance = self.material.radiance();
+        let (w, one_minus_cos_max) = match self.cone(&hit.hit_point) {
+            Some(cone) => cone,
+            None => {
+                let normal = self.disk.normal(&Point3::origin());
+                let area = self.disk.area();
+                return (0..n)
+                    .map(|_| {
+                        let p = self.disk.get_samples(1)[0];
+                        area_sample(hit, &p, &normal, area, radiance)
+                    })
+                    .collect();
+            }
+        };
+        let pdf = 1.0 / (2.0 * PI * one_minus_cos_max);
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| {
+                let cos_theta = 1.0 - rng.gen::<f64>() * one_minus_cos_max;
+                let wi = cone_direction(&w, cos_theta, rng.gen());
+                match self.distance(&hit.hit_point, &wi) {
+                    Some(d) => LightSample::unless_shadowed(hit, wi, d, radiance, pdf),
+                    None => LightSample::missed(wi),
+                }
+            })
+            .collect()
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
+    }
+
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let record = self
+            .disk
+            .intersects(&hit.spawn_ray(*wi), 0.0, f64::INFINITY);
+        let record = match record {
+            Some(record) => record,
+            None => return Some(LightSample::missed(*wi)),
+        };
+        let radiance = self.material.radiance();
+        Some(match self.cone(&hit.hit_point) {
+            Some((_, one_minus_cos_max)) => {
+                let r = (record.hit_point - hit.hit_point).norm();
+                let pdf = 1.0 / (2.0 * PI * one_minus_cos_max);
+                LightSample::unless_shadowed(hit, *wi, r, radiance, pdf)
+            }
+            None => area_sample(
+                hit,
+                &record.hit_point,
+                &record.geometric_normal,
+                self.disk.area(),
+                radiance,
+            ),
+        })
+    }
+
+    // emitters shine from both faces
+    fn power(&self, _scene_radius: f64) -> f64 {
+        2.0 * PI * self.disk.area() * luminance(&self.material.radiance())
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            bounds: self.disk.get_bounding_box(),
+            w: self.disk.normal(&self.disk.get_center()),
+            phi: self.power(0.0),
+            cos_theta_o: 1.0,
+            cos_theta_e: 0.0,
+            two_sided: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::light::tests::{hit, irradiance, origin_ray, world};
+
+    // disk of radius r facing the origin at distance h, E = π·r²/(r² + h²)
+    fn check(r: f64, h: f64) {
+        // scene units of half the normalised ones, centered on the origin
+        let center = Point3::new(1.0, 1.0, 1.0 + h);
+        let disk = Arc::new(Disk::new(0, center, Vec3::z(), r, 2.0));
+        let world = world(vec![disk.clone()]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let light = DiskLight::new(disk, Emissive::new(1.0, Color::repeat(1.0)));
+        let expected = PI * r * r / (r * r + h * h);
+        let estimate = irradiance(&light, &hit, 20_000);
+        assert!(
+            (estimate - expected).abs() < 0.03 * expected,
+            "{} {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn cone_sampling_matches_the_irradiance_of_a_disk() {
+        check(0.5, 1.0);
+        check(0.1, 3.0);
+    }
+
+    #[test]
+    fn area_sampling_matches_the_irradiance_of_a_disk() {
+        // the origin lies inside the sphere bounding the disk
+        check(2.0, 1.0);
+    }
+}
@@ -0,0 +1,215 @@
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+
+use crate::color::{luminance, Color};
+use crate::error::SceneError;
+use crate::geometric_object::scale_direction;
+use crate::light::{Background, Light, LightSample};
+use crate::model::Vec3;
+use crate::ray::RayHit;
+use crate::sampler::Distribution;
+
+// Light from an equirectangular image around the scene, with the scene's up
+// axis at the top row. Directions are importance sampled by luminance, a
+// distribution over the rows picks a row and one per row a column
+pub struct EnvironmentLight {
+    pixels: Vec<[f32; 3]>,
+    width: usize,
+    height: usize,
+    rotation: f64, // radians about the up axis
+    intensity: f64,
+    rows: Distribution,
+    columns: Vec<Distribution>,
+    sample_points_sqrt: usize,
+}
+
+impl EnvironmentLight {
+    pub fn new(
+        pixels: Vec<[f32; 3]>,
+        width: usize,
+        rotation: f64,
+        intensity: f64,
+    ) -> Result<EnvironmentLight, SceneError> {
+        let height = pixels.len().checked_div(width).unwrap_or(0);
+        if height == 0 || width * height != pixels.len() {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: format!(
+                    "environment map has {} pixels in rows of {}",
+                    pixels.len(),
+                    width
+                ),
+            });
+        }
+        // rows near the poles cover less of the sphere
+        let columns: Vec<Distribution> = (0..height)
+            .map(|y| {
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+                let weights: Vec<f64> = pixels[y * width..(y + 1) * width]
+                    .iter()
+                    .map(|&[r, g, b]| {
+                        luminance(&Color::new(r as f64, g as f64, b as f64)) * sin_theta
+                    })
+                    .collect();
+                Distribution::new(&weights)
+            })
+            .collect();
+        let row_weights: Vec<f64> = columns.iter().map(|c| c.total()).collect();
+        Ok(EnvironmentLight {
+            rows: Distribution::new(&row_weights),
+            columns,
+            pixels,
+            width,
+            height,
+            rotation,
+            intensity,
+            sample_points_sqrt: 5,
+        })
+    }
+
+    // pixel seen along a world direction
+    fn pixel(&self, dir: &Vec3) -> (usize, usize) {
+        let d = scale_direction(dir.normalize());
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let phi = (d.z.atan2(d.x) - self.rotation).rem_euclid(2.0 * PI);
+        let x = (phi / (2.0 * PI) * self.width as f64) as usize;
+        let y = (theta / PI * self.height as f64) as usize;
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+
+    fn texel(&self, x: usize, y: usize) -> Color {
+        let [r, g, b] = self.pixels[y * self.width + x];
+        Color::new(r as f64, g as f64, b as f64) * self.intensity
+    }
+
+    // solid angle density of sampling a world direction
+    pub fn pdf(&self, dir: &Vec3) -> f64 {
+        let (x, y) = self.pixel(dir);
+        let cos_theta = scale_direction(dir.normalize()).y;
+        self.texel_pdf(x, y, (1.0 - cos_theta * cos_theta).max(0.0).sqrt())
+    }
+
+    // density over the unit square converted to the sphere
+    fn texel_pdf(&self, x: usize, y: usize, sin_theta: f64) -> f64 {
+        if self.rows.total() <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let pdf_uv = self.rows.pdf(y) * self.columns[y].pdf(x) * (self.width * self.height) as f64;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+impl Background for EnvironmentLight {
+    fn radiance(&self, dir: &Vec3) -> Color {
+        let (x, y) = self.pixel(dir);
+        self.texel(x, y)
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        let mut rng = thread_rng();
+        (0..n)
+            .filter_map(|_| {
+                let y = self.rows.sample(rng.gen())?;
+                let x = self.columns[y].sample(rng.gen())?;
+                // anywhere within the pixel
+                let theta = PI * (y as f64 + rng.gen::<f64>()) / self.height as f64;
+                let phi =
+                    2.0 * PI * (x as f64 + rng.gen::<f64>()) / self.width as f64 + self.rotation;
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let wi = scale_direction(Vec3::new(
+                    sin_theta * phi.cos(),
+                    cos_theta,
+                    sin_theta * phi.sin(),
+                ));
+                let pdf = self.texel_pdf(x, y, sin_theta);
+                if pdf <= 0.0 {
+                    return None;
+                }
+                let radiance = self.texel(x, y);
+                Some(LightSample::unless_shadowed(
+                    hit,
+                    wi,
+                    f64::INFINITY,
+                    radiance,
+                    pdf,
+                ))
+            })
+            .collect()
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
+    }
+
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let pdf = self.pdf(wi);
+        if pdf <= 0.0 {
+            return Some(LightSample::missed(*wi));
+        }
+        let radiance = self.radiance(wi);
+        Some(LightSample::unless_shadowed(
+            hit,
+            *wi,
+            f64::INFINITY,
+            radiance,
+            pdf,
+        ))
+    }
+
+    // the radiance integrated over the sphere falling on a disk
+    fn power(&self, scene_radius: f64) -> f64 {
+        let pixel_solid_angle = 2.0 * PI * PI / (self.width * self.height) as f64;
+        PI * scene_radius * scene_radius * self.rows.total() * pixel_solid_angle * self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::tests::over_directions;
+
+    // uniform map with one bright pixel
+    fn map(width: usize, height: usize, bright: (usize, usize)) -> Vec<[f32; 3]> {
+        let mut pixels = vec![[1.0, 0.5, 0.25]; width * height];
+        pixels[bright.1 * width + bright.0] = [10.0, 5.0, 2.5];
+        pixels
+    }
+
+    // world direction through the center of a pixel
+    fn direction(light: &EnvironmentLight, x: usize, y: usize) -> Vec3 {
+        let theta = PI * (y as f64 + 0.5) / light.height as f64;
+        let phi = 2.0 * PI * (x as f64 + 0.5) / light.width as f64 + light.rotation;
+        scale_direction(Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        ))
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        for rotation in [0.0, 1.0] {
+            let light = EnvironmentLight::new(map(16, 8, (3, 2)), 16, rotation, 2.0).unwrap();
+            let total = over_directions(|wi| light.pdf(wi), 200);
+            assert!((total - 1.0).abs() < 0.01, "{}", total);
+        }
+    }
+
+    #[test]
+    fn pdf_follows_the_luminance() {
+        let light = EnvironmentLight::new(map(16, 8, (3, 2)), 16, 1.0, 2.0).unwrap();
+        let bright = direction(&light, 3, 2);
+        let dim = direction(&light, 9, 2);
+        assert!((light.pdf(&bright) / light.pdf(&dim) - 10.0).abs() < 1e-9);
+        assert_eq!(light.radiance(&bright), Color::new(20.0, 10.0, 5.0));
+        assert_eq!(light.radiance(&dim), Color::new(2.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn pixel_count_must_fill_the_rows() {
+        assert!(EnvironmentLight::new(map(16, 8, (0, 0)), 15, 0.0, 1.0).is_err());
+        assert!(EnvironmentLight::new(vec![], 16, 0.0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::f64::consts::PI;
+
+use crate::geometric_object::plane_axes;
+use crate::model::Vec3;
+
+// A measured goniometric distribution in type C photometry: candela at
+// vertical angles from the nadir and horizontal angles about it, both in
+// degrees. Horizontal angles ending at 0, 90 or 180 stand for a profile that
+// is symmetric about the nadir, about both planes or about the 0-180 plane,
+// and angles running from 90 to 270 for one symmetric about the 90-270 plane
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    candela: Vec<f64>, // a row of vertical angles per horizontal angle
+    max_candela: f64,
+    solid_angle: f64,
+}
+
+impl IesProfile {
+    pub fn new(vertical: Vec<f64>, horizontal: Vec<f64>, candela: Vec<f64>) -> IesProfile {
+        let max_candela = candela.iter().copied().fold(0.0, f64::max);
+        let mut profile = IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            max_candela,
+            solid_angle: 0.0,
+        };
+        profile.solid_angle = profile.integrate();
+        profile
+    }
+
+    // the brightest direction, the intensity of the file's own photometry
+    pub fn max_candela(&self) -> f64 {
+        self.max_candela
+    }
+
+    // the relative intensity integrated over the sphere, lumens per candela
+    pub fn solid_angle(&self) -> f64 {
+        self.solid_angle
+    }
+
+    // Intensity towards wo relative to the brightest direction, with the
+    // nadir along `nadir`. The 0° horizontal angle follows plane_axes
+    pub fn relative(&self, nadir: &Vec3, wo: &Vec3) -> f64 {
+        let (a, b) = plane_axes(nadir);
+        let theta = nadir.dot(wo).clamp(-1.0, 1.0).acos();
+        let phi = wo.dot(&b).atan2(wo.dot(&a));
+        self.relative_at(theta.to_degrees(), phi.to_degrees())
+    }
+
+    fn relative_at(&self, theta: f64, phi: f64) -> f64 {
+        if self.max_candela <= 0.0 {
+            return 0.0;
+        }
+        let (first, last) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if theta < first || theta > last {
+            return 0.0;
+        }
+        let phi = self.fold_horizontal(phi.rem_euclid(360.0));
+        let (h0, h1, s) = bracket(&self.horizontal, phi);
+        let (v0, v1, t) = bracket(&self.vertical, theta);
+        let n = self.vertical.len();
+        let at = |h: usize, v: usize| self.candela[h * n + v];
+        let row = |h: usize| at(h, v0) * (1.0 - t) + at(h, v1) * t;
+        (row(h0) * (1.0 - s) + row(h1) * s) / self.max_candela
+    }
+
+    // brings phi in [0, 360) into the range the horizontal angles cover
+    fn fold_horizontal(&self, phi: f64) -> f64 {
+        let (first, last) = (
+            self.horizontal[0],
+            self.horizontal[self.horizontal.len() - 1],
+        );
+        if first >= 90.0 {
+            return if phi < 90.0 {
+                180.0 - phi
+            } else if phi > 270.0 {
+                540.0 - phi
+            } else {
+                phi
+            };
+        }
+        let phi = if last <= 180.0 && phi > 180.0 {
+            360.0 - phi
+        } else {
+            phi
+        };
+        if last <= 90.0 && phi > 90.0 {
+            180.0 - phi
+        } else {
+            phi
+        }
+    }
+
+    fn integrate(&self) -> f64 {
+        let (n_theta, n_phi) = (180, 72);
+        let d_theta = PI / n_theta as f64;
+        let d_phi = 2.0 * PI / n_phi as f64;
+        (0..n_theta)
+            .map(|i| {
+                let theta = (i as f64 + 0.5) * d_theta;
+                let ring: f64 = (0..n_phi)
+                    .map(|j| {
+                        let phi = (j as f64 + 0.5) * d_phi;
+                        self.relative_at(theta.to_degrees(), phi.to_degrees())
+                    })
+                    .sum();
+                ring * theta.sin() * d_theta * d_phi
+            })
+            .sum()
+    }
+}
+
+// the angles either side of x and how far x lies between them
+fn bracket(angles: &[f64], x: f64) -> (usize, usize, f64) {
+    let i = angles.partition_point(|a| *a <= x);
+    if i == 0 {
+        return (0, 0, 0.0);
+    }
+    if i == angles.len() {
+        return (i - 1, i - 1, 0.0);
+    }
+    let (a0, a1) = (angles[i - 1], angles[i]);
+    (i - 1, i, (x - a0) / (a1 - a0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // brighter towards the first vertical angle of each row
+    fn profile(horizontal: Vec<f64>, rows: &[f64]) -> IesProfile {
+        let candela = rows.iter().flat_map(|c| [*c, c / 2.0]).collect();
+        IesProfile::new(vec![0.0, 90.0], horizontal, candela)
+    }
+
+    #[test]
+    fn profile_symmetric_about_the_90_270_plane_is_mirrored() {
+        let profile = profile(vec![90.0, 180.0, 270.0], &[10.0, 100.0, 20.0]);
+        assert_eq!(profile.relative_at(0.0, 180.0), 1.0);
+        assert_eq!(profile.relative_at(0.0, 0.0), 1.0);
+        assert_eq!(
+            profile.relative_at(0.0, 45.0),
+            profile.relative_at(0.0, 135.0)
+        );
+        assert_eq!(
+            profile.relative_at(0.0, 315.0),
+            profile.relative_at(0.0, 225.0)
+        );
+        assert_eq!(profile.relative_at(0.0, 270.0), 0.2);
+    }
+
+    #[test]
+    fn profile_symmetric_about_one_plane_is_mirrored() {
+        let profile = profile(vec![0.0, 90.0, 180.0], &[10.0, 100.0, 20.0]);
+        assert_eq!(profile.relative_at(0.0, 270.0), 1.0);
+        assert_eq!(
+            profile.relative_at(0.0, 300.0),
+            profile.relative_at(0.0, 60.0)
+        );
+    }
+
+    #[test]
+    fn solid_angle_covers_the_lit_directions() {
+        // even everywhere, then only below the horizontal
+        let everywhere = IesProfile::new(vec![0.0, 180.0], vec![0.0], vec![5.0, 5.0]);
+        assert!((everywhere.solid_angle() - 4.0 * PI).abs() < 1e-3);
+        let downlight = IesProfile::new(vec![0.0, 90.0], vec![0.0], vec![5.0, 5.0]);
+        assert!((downlight.solid_angle() - 2.0 * PI).abs() < 1e-3);
+        let nadir = Vec3::new(0.0, -1.0, 0.0);
+        assert_eq!(downlight.relative(&nadir, &Vec3::new(0.6, -0.8, 0.0)), 1.0);
+        assert_eq!(downlight.relative(&nadir, &Vec3::new(0.6, 0.8, 0.0)), 0.0);
+    }
+}
@@ -0,0 +1,217 @@
+use nalgebra::Point3;
+use rand::Rng;
+use std::sync::Arc;
+
+use crate::light::{Light, LightBounds};
+use crate::model::Vec3;
+use crate::sampler::Distribution;
+
+// How the lights are chosen at a shading point. `All` shades every light,
+// the others pick one light, by its power or by its importance to the point
+// through a light BVH, so the cost doesn't grow with the number of lights
+#[derive(Clone, Copy)]
+pub enum LightSelection {
+    All,
+    Power,
+    Bvh,
+}
+
+// Picks a light with odds that are known, the shading divides by them
+pub struct LightSampler {
+    power: Distribution,
+    bvh: Option<LightNode>,
+    infinite: Vec<usize>, // lights without bounds, kept out of the BVH
+}
+
+enum LightNode {
+    Leaf {
+        light: usize,
+        bounds: LightBounds,
+    },
+    Interior {
+        bounds: LightBounds,
+        children: Box<[LightNode; 2]>,
+    },
+}
+
+impl LightNode {
+    // Splits at the median centroid along the widest axis of the centroids
+    fn new(mut lights: Vec<(usize, LightBounds)>) -> LightNode {
+        if lights.len() == 1 {
+            let (light, bounds) = lights.remove(0);
+            return LightNode::Leaf { light, bounds };
+        }
+        let centroids: Vec<Point3<f64>> = lights.iter().map(|(_, b)| b.centroid()).collect();
+        let min = centroids.iter().fold(centroids[0], |a, c| a.inf(c));
+        let max = centroids.iter().fold(centroids[0], |a, c| a.sup(c));
+        let axis = (max - min).imax();
+        lights
+            .sort_by(|(_, a), (_, b)| a.centroid()[axis].partial_cmp(&b.centroid()[axis]).unwrap());
+        let right = lights.split_off(lights.len() / 2);
+        let children = Box::new([LightNode::new(lights), LightNode::new(right)]);
+        let bounds = LightBounds::union(children[0].bounds(), children[1].bounds());
+        LightNode::Interior { bounds, children }
+    }
+
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } | LightNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+impl LightSampler {
+    // scene_radius is what lights at infinity shine on, for their power
+    pub fn new(lights: &[Arc<dyn Light + Send + Sync>], scene_radius: f64) -> LightSampler {
+        let powers: Vec<f64> = lights.iter().map(|l| l.power(scene_radius)).collect();
+        let mut infinite = vec![];
+        let mut bounded = vec![];
+        for (i, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(bounds) if bounds.phi > 0.0 => bounded.push((i, bounds)),
+                Some(_) => {}
+                None => infinite.push(i),
+            }
+        }
+        LightSampler {
+            power: Distribution::new(&powers),
+            bvh: if bounded.is_empty() {
+                None
+            } else {
+                Some(LightNode::new(bounded))
+            },
+            infinite,
+        }
+    }
+
+    // a light in proportion to its power, with the odds of picking it
+    pub fn sample_power<R: Rng>(&self, rng: &mut R) -> Option<(usize, f64)> {
+        let i = self.power.sample(rng.gen())?;
+        Some((i, self.power.pdf(i)))
+    }
+
+    // Lights at infinity are picked uniformly alongside the BVH as a whole,
+    // within the BVH each node is chosen by the importance of its bounds to
+    // p on a surface facing n
+    pub fn sample_bvh<R: Rng>(
+        &self,
+        p: &Point3<f64>,
+        n: &Vec3,
+        rng: &mut R,
+    ) -> Option<(usize, f64)> {
+        let choices = self.infinite.len() + usize::from(self.bvh.is_some());
+        if choices == 0 {
+            return None;
+        }
+        let pick = rng.gen_range(0..choices);
+        let mut pmf = 1.0 / choices as f64;
+        if pick < self.infinite.len() {
+            return Some((self.infinite[pick], pmf));
+        }
+        let mut node = self.bvh.as_ref()?;
+        loop {
+            match node {
+                LightNode::Leaf { light, bounds } => {
+                    return if bounds.importance(p, n) > 0.0 {
+                        Some((*light, pmf))
+                    } else {
+                        None
+                    };
+                }
+                LightNode::Interior { children, .. } => {
+                    let left = children[0].bounds().importance(p, n);
+                    let right = children[1].bounds().importance(p, n);
+                    if left + right <= 0.0 {
+                        return None;
+                    }
+                    let p_left = left / (left + right);
+                    if rng.gen::<f64>() < p_left {
+                        pmf *= p_left;
+                        node = &children[0];
+                    } else {
+                        pmf *= 1.0 - p_left;
+                        node = &children[1];
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::light::{DirectionalLight, Intensity, PointLight};
+
+    fn lights() -> Vec<Arc<dyn Light + Send + Sync>> {
+        let point = |x: f64, y: f64, cd: f64| {
+            let location = Point3::new(x, y, 1.0);
+            let light = PointLight::new(Intensity::Candela(cd), Color::repeat(1.0), location, None);
+            Arc::new(light) as Arc<dyn Light + Send + Sync>
+        };
+        let sun = DirectionalLight {
+            ls: 1.0,
+            cl: Color::repeat(1.0),
+            direction: Vec3::z(),
+        };
+        vec![
+            point(0.0, 0.0, 1.0),
+            point(0.5, 0.2, 2.0),
+            Arc::new(sun),
+            point(-0.4, 0.7, 4.0),
+            point(3.0, -2.0, 1.0),
+        ]
+    }
+
+    // the odds each light was returned with, checked against how often it was
+    fn check_pmf(mut sample: impl FnMut() -> Option<(usize, f64)>, n: usize) {
+        let runs = 20_000;
+        let mut pmfs = HashMap::new();
+        let mut counts = vec![0; n];
+        for _ in 0..runs {
+            let (i, pmf) = sample().unwrap();
+            let seen = *pmfs.entry(i).or_insert(pmf);
+            assert!((seen - pmf).abs() < 1e-12);
+            counts[i] += 1;
+        }
+        let sum: f64 = pmfs.values().sum();
+        assert!((sum - 1.0).abs() < 1e-9, "{}", sum);
+        for (i, pmf) in pmfs {
+            let frequency = counts[i] as f64 / runs as f64;
+            assert!(
+                (frequency - pmf).abs() < 0.02,
+                "{} {} {}",
+                i,
+                frequency,
+                pmf
+            );
+        }
+    }
+
+    #[test]
+    fn power_pmf_sums_to_one() {
+        let lights = lights();
+        let sampler = LightSampler::new(&lights, 1.0);
+        let mut rng = thread_rng();
+        check_pmf(|| sampler.sample_power(&mut rng), lights.len());
+    }
+
+    #[test]
+    fn bvh_pmf_sums_to_one() {
+        let lights = lights();
+        let sampler = LightSampler::new(&lights, 1.0);
+        let mut rng = thread_rng();
+        let p = Point3::origin();
+        check_pmf(
+            || sampler.sample_bvh(&p, &Vec3::z(), &mut rng),
+            lights.len(),
+        );
+        assert!(LightSampler::new(&[], 1.0)
+            .sample_bvh(&p, &Vec3::z(), &mut rng)
+            .is_none());
+    }
+}
@@ -1,22 +1,194 @@
 use crate::color::Color;
 use crate::model::Vec3;
-use crate::ray::RayHit;
+use crate::ray::{error_bound, RayHit};
 
 pub mod ambient;
 pub mod ambient_occuluder;
 pub mod area;
+pub mod bounds;
 pub mod directional;
+pub mod disk;
+pub mod environment;
+pub mod ies;
+pub mod light_sampler;
 pub mod point;
+pub mod rectangle;
+pub mod sphere;
+pub mod spot;
+pub mod sun_sky;
 
 pub use ambient::*;
 pub use ambient_occuluder::*;
 pub use area::*;
+pub use bounds::*;
 pub use directional::*;
+pub use disk::*;
+pub use environment::*;
+pub use ies::*;
+pub use light_sampler::*;
 pub use point::*;
+pub use rectangle::*;
+pub use sphere::*;
+pub use spot::*;
+pub use sun_sky::*;
+
+// Light arriving at a hit point from one direction. Occluded samples carry
+// no radiance
+pub struct LightSample {
+    pub wi: Vec3, // towards the light
+    pub radiance: Color,
+    pub pdf: f64, // solid angle density of wi, 1 for lights from a single direction
+}
+
+impl LightSample {
+    // a sample whose radiance only arrives if nothing is in the way for
+    // `distance` along wi
+    pub fn unless_shadowed(
+        hit: &RayHit,
+        wi: Vec3,
+        distance: f64,
+        radiance: Color,
+        pdf: f64,
+    ) -> LightSample {
+        // The spawned origin lies up to the hit's offset from where distance
+        // was measured, and the point on the light is only known up to the
+        // rounding that placed it. Stopping short of both keeps the surface
+        // the light point lies on from shadowing it
+        let t_max = if distance.is_finite() {
+            let end = hit.hit_point + wi * distance;
+            distance
+                - hit.offset.norm()
+                - (error_bound(&hit.hit_point, 1) + error_bound(&end, 7)).norm()
+        } else {
+            distance
+        };
+        let visible = !hit.world.is_in_shadow(&hit.spawn_ray(wi), t_max);
+        LightSample {
+            wi,
+            radiance: if visible { radiance } else { Color::zeros() },
+            pdf,
+        }
+    }
+
+    // a direction that doesn't reach the light
+    pub fn missed(wi: Vec3) -> LightSample {
+        LightSample {
+            wi,
+            radiance: Color::zeros(),
+            pdf: 0.0,
+        }
+    }
+}
 
 pub trait Light {
-    // the direction of the incoming light at a hit point
-    fn get_direction(&self, hit: &RayHit) -> Vec3;
-    fn radiance(&self, hit: &RayHit) -> Color;
-    fn shadow_amount(&self, hit: &RayHit) -> f64;
+    // The shading averages radiance·f·cosθ/pdf over n samples. Lights at a
+    // single point or from a single direction give one whatever n is
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample>;
+
+    // samples per shading point, split with the BRDF under MIS
+    fn sample_count(&self) -> usize {
+        1
+    }
+
+    // Light arriving along wi when something else, such as the BRDF, picked
+    // the direction, with the density `sample` would give wi. None for lights
+    // at a single point or from a single direction, which no direction finds
+    fn sample_direction(&self, _hit: &RayHit, _wi: &Vec3) -> Option<LightSample> {
+        None
+    }
+
+    // Rough luminous power, the odds of picking the light among others.
+    // Lights at infinity count what falls on a disk of scene_radius
+    fn power(&self, scene_radius: f64) -> f64;
+
+    // where the light is and which way it shines, None for lights at infinity
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+// radiance arriving along rays that leave the scene
+pub trait Background {
+    fn radiance(&self, dir: &Vec3) -> Color;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nalgebra::Point3;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::geometric_object::Geometry;
+    use crate::material::{Emissive, Material};
+    use crate::ray::Ray;
+    use crate::view_plane::ViewPlane;
+    use crate::world::World;
+
+    // a world of emitters of radiance 1 with material id 0, nothing else to shadow
+    pub fn world(geometries: Vec<Arc<dyn Geometry + Send + Sync>>) -> World {
+        let emissive = Material::Emissive(Emissive::new(1.0, Color::repeat(1.0)));
+        let vp = ViewPlane {
+            hres: 10,
+            vres: 10,
+            pixel_size: 1.0,
+        };
+        let ambient_light = AmbientLight {
+            ls: 0.0,
+            cl: Color::zeros(),
+        };
+        let materials = HashMap::from([(0, Box::new(emissive))]);
+        World::new(vp, geometries, vec![], ambient_light, materials).unwrap()
+    }
+
+    // a hit at the origin of the ray, on a surface facing `normal`
+    pub fn hit<'a>(world: &'a World, ray: &'a Ray, normal: Vec3) -> RayHit<'a> {
+        RayHit {
+            ray,
+            hit_point: ray.origin,
+            normal,
+            offset: Vec3::zeros(),
+            inside: false,
+            world,
+            depth: 0,
+            material_id: 0,
+            vertex_color: None,
+        }
+    }
+
+    // Monte Carlo estimate of the irradiance the light's samples bring to the hit
+    pub fn irradiance(light: &dyn Light, hit: &RayHit, n: usize) -> f64 {
+        let samples = light.sample(hit, n);
+        let sum: f64 = samples
+            .iter()
+            .filter(|s| s.pdf > 0.0)
+            .map(|s| s.radiance.x * hit.normal.dot(&s.wi).max(0.0) / s.pdf)
+            .sum();
+        sum / samples.len() as f64
+    }
+
+    // integral of a density over all directions, by the midpoint rule in
+    // (cosθ, φ) where the measure is uniform
+    pub fn over_directions(f: impl Fn(&Vec3) -> f64, n: usize) -> f64 {
+        let d_cos = 2.0 / n as f64;
+        let d_phi = 2.0 * std::f64::consts::PI / (2 * n) as f64;
+        let sum: f64 = (0..n)
+            .flat_map(|i| (0..2 * n).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let cos_theta = -1.0 + (i as f64 + 0.5) * d_cos;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = (j as f64 + 0.5) * d_phi;
+                f(&Vec3::new(
+                    sin_theta * phi.cos(),
+                    sin_theta * phi.sin(),
+                    cos_theta,
+                ))
+            })
+            .sum();
+        sum * d_cos * d_phi
+    }
+
+    pub fn origin_ray() -> Ray {
+        Ray::new(Point3::origin(), Vec3::z())
+    }
 }
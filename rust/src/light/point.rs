@@ -1,29 +1,128 @@
 use nalgebra::{distance, Point3};
+use std::f64::consts::PI;
+use std::sync::Arc;
 
-use crate::color::Color;
-use crate::light::Light;
+use crate::aabb::AABB;
+use crate::color::{luminance, Color};
+use crate::geometric_object::scale_direction;
+use crate::light::{IesProfile, Light, LightBounds, LightSample};
 use crate::model::Vec3;
 use crate::ray::RayHit;
 
+// Brightness of a point or spot light. Candela is the intensity in its
+// brightest direction, falling off with the squared distance in scene units.
+// Lumens is the power it sends out, spread over the directions it reaches
+#[derive(Clone, Copy)]
+pub enum Intensity {
+    Candela(f64),
+    Lumens(f64),
+}
+
+impl Intensity {
+    // `solid_angle` is the light's intensity relative to its brightest
+    // direction, integrated over the sphere
+    pub fn candela(self, solid_angle: f64) -> f64 {
+        match self {
+            Intensity::Candela(cd) => cd,
+            Intensity::Lumens(lm) if solid_angle > 0.0 => lm / solid_angle,
+            Intensity::Lumens(_) => 0.0,
+        }
+    }
+}
+
+// A light at a single point, shining equally everywhere or, with a measured
+// profile, with the profile's nadir straight down
 pub struct PointLight {
-    pub ls: f64,
     pub cl: Color,
     pub location: Point3<f64>,
+    candela: f64,
+    profile: Option<Arc<IesProfile>>,
+}
+
+impl PointLight {
+    pub fn new(
+        intensity: Intensity,
+        cl: Color,
+        location: Point3<f64>,
+        profile: Option<Arc<IesProfile>>,
+    ) -> PointLight {
+        let solid_angle = profile.as_ref().map_or(4.0 * PI, |p| p.solid_angle());
+        PointLight {
+            cl,
+            location,
+            candela: intensity.candela(solid_angle),
+            profile,
+        }
+    }
 }
 
 impl Light for PointLight {
-    fn get_direction(&self, hit: &RayHit) -> Vec3 {
-        (self.location - hit.hit_point).normalize()
+    // inverse square falloff
+    fn sample(&self, hit: &RayHit, _n: usize) -> Vec<LightSample> {
+        let relative = self.profile.as_ref().map_or(1.0, |p| {
+            let wo = (hit.hit_point - self.location).normalize();
+            p.relative(&scale_direction(-Vec3::y()), &wo)
+        });
+        vec![point_sample(
+            hit,
+            &self.location,
+            self.cl * (self.candela * relative),
+        )]
     }
 
-    fn radiance(&self, _hit: &RayHit) -> Color {
-        self.cl * self.ls
+    fn power(&self, _scene_radius: f64) -> f64 {
+        let solid_angle = self.profile.as_ref().map_or(4.0 * PI, |p| p.solid_angle());
+        solid_angle * self.candela * luminance(&self.cl)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            bounds: AABB::new(self.location, self.location),
+            w: Vec3::z(),
+            phi: self.power(0.0),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        })
+    }
+}
+
+// sample of a light at a single point, `intensity` falls off with the square
+// of the distance
+pub fn point_sample(hit: &RayHit, location: &Point3<f64>, intensity: Color) -> LightSample {
+    let wi = (location - hit.hit_point).normalize();
+    let d = distance(location, &hit.hit_point);
+    LightSample::unless_shadowed(hit, wi, d, intensity / (d * d), 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometric_object::Sphere;
+    use crate::light::tests::{hit, irradiance, origin_ray, world};
+
+    #[test]
+    fn irradiance_falls_off_with_the_squared_distance() {
+        // an emitter out of the way so the world isn't empty
+        let sphere = Arc::new(Sphere::new(0, 1.0, Point3::origin(), 10.0));
+        let world = world(vec![sphere]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let cd = 4.0;
+        for d in [0.5, 1.0, 2.0] {
+            let location = Point3::new(0.0, 0.0, d);
+            let light = PointLight::new(Intensity::Candela(cd), Color::repeat(1.0), location, None);
+            let expected = cd / (d * d);
+            assert!((irradiance(&light, &hit, 1) - expected).abs() < 1e-9 * expected);
+        }
     }
 
-    fn shadow_amount(&self, hit: &RayHit) -> f64 {
-        let direction = (self.location - hit.hit_point).normalize();
-        let d = distance(&self.location, &hit.hit_point);
-        let b = hit.world.is_in_shadow(&hit.hit_point, &direction, d);
-        f64::from(u32::from(!b))
+    #[test]
+    fn lumens_are_spread_over_the_sphere() {
+        let location = Point3::origin();
+        let light = PointLight::new(Intensity::Lumens(100.0), Color::repeat(1.0), location, None);
+        assert!((light.candela - 100.0 / (4.0 * PI)).abs() < 1e-12);
+        assert!((light.power(0.0) - 100.0).abs() < 1e-9);
+        assert_eq!(Intensity::Lumens(100.0).candela(0.0), 0.0);
     }
 }
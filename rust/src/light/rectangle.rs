@@ -0,0 +1,239 @@
+use nalgebra::Point3;
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::color::luminance;
+use crate::geometric_object::{Geometry, Rectangle};
+use crate::light::{area_sample, Light, LightBounds, LightSample};
+use crate::material::Emissive;
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+// An emitting rectangle sampled uniformly over its solid angle, after
+// Ureña et al., An Area-Preserving Parametrization for Spherical Rectangles.
+// Parallelograms that aren't rectangles fall back to area sampling
+pub struct RectangleLight {
+    rectangle: Arc<Rectangle>,
+    sample_points_sqrt: usize,
+    pub material: Emissive,
+}
+
+impl RectangleLight {
+    pub fn new(rectangle: Arc<Rectangle>, material: Emissive) -> RectangleLight {
+        RectangleLight {
+            rectangle,
+            sample_points_sqrt: 5,
+            material,
+        }
+    }
+
+    // None when o lies in the plane of the light or the edges aren't square
+    fn spherical(&self, o: &Point3<f64>) -> Option<SphericalRectangle> {
+        let (p0, a, b) = self.rectangle.edges();
+        if a.dot(&b).abs() <= 1e-9 * a.norm() * b.norm() {
+            SphericalRectangle::new(o, &p0, &a, &b)
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for RectangleLight {
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        let (p0, _, _) = self.rectangle.edges();
+        let radiance = self.material.radiance();
+        let mut rng = thread_rng();
+        match self.spherical(&hit.hit_point) {
+            Some(s) => (0..n)
+                .map(|_| {
+                    let p = s.sample(rng.gen(), rng.gen());
+                    let d = p - hit.hit_point;
+                    let r = d.norm();
+                    LightSample::unless_shadowed(hit, d / r, r, radiance, 1.0 / s.solid_angle)
+                })
+                .collect(),
+            // the hit point lies in the plane of the light or the edges aren't square
+            None => {
+                let normal = self.rectangle.normal(&p0);
+                let area = self.rectangle.area();
+                (0..n)
+                    .map(|_| {
+                        let p = self.rectangle.get_samples(1)[0];
+                        area_sample(hit, &p, &normal, area, radiance)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
+    }
+
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let record = self
+            .rectangle
+            .intersects(&hit.spawn_ray(*wi), 0.0, f64::INFINITY);
+        let record = match record {
+            Some(record) => record,
+            None => return Some(LightSample::missed(*wi)),
+        };
+        let radiance = self.material.radiance();
+        Some(match self.spherical(&hit.hit_point) {
+            Some(s) => {
+                let r = (record.hit_point - hit.hit_point).norm();
+                LightSample::unless_shadowed(hit, *wi, r, radiance, 1.0 / s.solid_angle)
+            }
+            None => area_sample(
+                hit,
+                &record.hit_point,
+                &record.geometric_normal,
+                self.rectangle.area(),
+                radiance,
+            ),
+        })
+    }
+
+    // emitters shine from both faces
+    fn power(&self, _scene_radius: f64) -> f64 {
+        2.0 * PI * self.rectangle.area() * luminance(&self.material.radiance())
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            bounds: self.rectangle.get_bounding_box(),
+            w: self.rectangle.normal(&self.rectangle.get_center()),
+            phi: self.power(0.0),
+            cos_theta_o: 1.0,
+            cos_theta_e: 0.0,
+            two_sided: true,
+        })
+    }
+}
+
+// the rectangle seen from o, in a frame with the rectangle at z = z0 < 0
+struct SphericalRectangle {
+    o: Point3<f64>,
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+    z0: f64,
+    x0: f64,
+    x1: f64,
+    y0: f64,
+    y1: f64,
+    b0: f64,
+    b1: f64,
+    k: f64,
+    solid_angle: f64,
+}
+
+impl SphericalRectangle {
+    fn new(o: &Point3<f64>, s: &Point3<f64>, a: &Vec3, b: &Vec3) -> Option<SphericalRectangle> {
+        let (x, y) = (a.normalize(), b.normalize());
+        let mut z = x.cross(&y);
+        let d = s - o;
+        let mut z0 = d.dot(&z);
+        if z0 > 0.0 {
+            z = -z;
+            z0 = -z0;
+        }
+        let (x0, y0) = (d.dot(&x), d.dot(&y));
+        let (x1, y1) = (x0 + a.norm(), y0 + b.norm());
+        let v00 = Vec3::new(x0, y0, z0);
+        let v01 = Vec3::new(x0, y1, z0);
+        let v10 = Vec3::new(x1, y0, z0);
+        let v11 = Vec3::new(x1, y1, z0);
+        let n0 = v00.cross(&v10).normalize();
+        let n1 = v10.cross(&v11).normalize();
+        let n2 = v11.cross(&v01).normalize();
+        let n3 = v01.cross(&v00).normalize();
+        let angle = |a: &Vec3, b: &Vec3| (-a.dot(b)).clamp(-1.0, 1.0).acos();
+        let (g0, g1, g2, g3) = (
+            angle(&n0, &n1),
+            angle(&n1, &n2),
+            angle(&n2, &n3),
+            angle(&n3, &n0),
+        );
+        let k = 2.0 * PI - g2 - g3;
+        let solid_angle = g0 + g1 - k;
+        if solid_angle.is_nan() || solid_angle <= 1e-12 {
+            return None;
+        }
+        Some(SphericalRectangle {
+            o: *o,
+            x,
+            y,
+            z,
+            z0,
+            x0,
+            x1,
+            y0,
+            y1,
+            b0: n0.z,
+            b1: n2.z,
+            k,
+            solid_angle,
+        })
+    }
+
+    // point on the rectangle for (u, v) in the unit square
+    fn sample(&self, u: f64, v: f64) -> Point3<f64> {
+        let au = u * self.solid_angle + self.k;
+        let fu = (au.cos() * self.b0 - self.b1) / au.sin();
+        let cu = (fu.signum() / (fu * fu + self.b0 * self.b0).sqrt()).clamp(-1.0, 1.0);
+        let xu = (-(cu * self.z0) / (1.0 - cu * cu).max(1e-12).sqrt()).clamp(self.x0, self.x1);
+        let d = (xu * xu + self.z0 * self.z0).sqrt();
+        let h0 = self.y0 / (d * d + self.y0 * self.y0).sqrt();
+        let h1 = self.y1 / (d * d + self.y1 * self.y1).sqrt();
+        let hv = h0 + v * (h1 - h0);
+        let yv = if hv * hv < 1.0 - 1e-12 {
+            (hv * d / (1.0 - hv * hv).sqrt()).clamp(self.y0, self.y1)
+        } else {
+            self.y1
+        };
+        self.o + self.x * xu + self.y * yv + self.z * self.z0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::light::tests::{hit, origin_ray, over_directions, world};
+
+    fn check(p0: Point3<f64>, a: Vec3, b: Vec3) {
+        // scene units of half the normalised ones, centered on the origin
+        let rectangle = Arc::new(Rectangle::new(0, p0 + Vec3::repeat(1.0), a, b, 2.0));
+        let world = world(vec![rectangle.clone()]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let light = RectangleLight::new(rectangle, Emissive::new(1.0, Color::repeat(1.0)));
+        let pdf = |wi: &Vec3| light.sample_direction(&hit, wi).unwrap().pdf;
+        let total = over_directions(pdf, 200);
+        assert!((total - 1.0).abs() < 0.01, "{}", total);
+        for s in light.sample(&hit, 100).iter().filter(|s| s.pdf > 0.0) {
+            assert!((pdf(&s.wi) - s.pdf).abs() < 1e-6 * s.pdf);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_solid_angle() {
+        check(Point3::new(-0.5, -0.5, 1.0), Vec3::x(), Vec3::y());
+        check(
+            Point3::new(0.2, -0.3, 0.5),
+            Vec3::new(0.8, 0.0, 0.6),
+            Vec3::y(),
+        );
+    }
+
+    #[test]
+    fn parallelograms_fall_back_to_area_sampling() {
+        check(
+            Point3::new(-0.5, -0.5, 1.0),
+            Vec3::x(),
+            Vec3::new(0.5, 1.0, 0.0),
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::color::luminance;
+use crate::geometric_object::{plane_axes, Geometry, Sphere};
+use crate::light::{Light, LightBounds, LightSample};
+use crate::material::Emissive;
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+// An emitting sphere sampled uniformly over the cone of directions it
+// subtends, so every sample lands on the visible side
+pub struct SphereLight {
+    sphere: Arc<Sphere>,
+    sample_points_sqrt: usize,
+    pub material: Emissive,
+}
+
+impl SphereLight {
+    pub fn new(sphere: Arc<Sphere>, material: Emissive) -> SphereLight {
+        SphereLight {
+            sphere,
+            sample_points_sqrt: 5,
+            material,
+        }
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        let radius = self.sphere.radius();
+        let to_center = self.sphere.get_center() - hit.hit_point;
+        let dc = to_center.norm();
+        let mut rng = thread_rng();
+        if dc <= radius {
+            // from inside light arrives from every direction
+            return (0..n)
+                .map(|_| {
+                    let cos_theta = 1.0 - 2.0 * rng.gen::<f64>();
+                    let wi = cone_direction(&Vec3::y(), cos_theta, rng.gen());
+                    // distance to the far side of the sphere
+                    let b = wi.dot(&to_center);
+                    let d = b + (b * b - dc * dc + radius * radius).max(0.0).sqrt();
+                    let radiance = self.material.radiance();
+                    LightSample::unless_shadowed(hit, wi, d, radiance, 1.0 / (4.0 * PI))
+                })
+                .collect();
+        }
+        let w = to_center / dc;
+        let sin2_max = radius * radius / (dc * dc);
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+        // 1 - cos_max without cancellation for small, distant spheres
+        let one_minus_cos_max = sin2_max / (1.0 + cos_max);
+        (0..n)
+            .map(|_| {
+                let cos_theta = 1.0 - rng.gen::<f64>() * one_minus_cos_max;
+                let wi = cone_direction(&w, cos_theta, rng.gen());
+                // distance to the near side of the sphere
+                let sin2_theta = (1.0 - cos_theta * cos_theta).max(0.0);
+                let d = dc * cos_theta - (radius * radius - dc * dc * sin2_theta).max(0.0).sqrt();
+                let pdf = 1.0 / (2.0 * PI * one_minus_cos_max);
+                LightSample::unless_shadowed(hit, wi, d, self.material.radiance(), pdf)
+            })
+            .collect()
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
+    }
+
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        let record = self
+            .sphere
+            .intersects(&hit.spawn_ray(*wi), 0.0, f64::INFINITY);
+        let record = match record {
+            Some(record) => record,
+            None => return Some(LightSample::missed(*wi)),
+        };
+        let radius = self.sphere.radius();
+        let dc = (self.sphere.get_center() - hit.hit_point).norm();
+        let pdf = if dc <= radius {
+            1.0 / (4.0 * PI)
+        } else {
+            let sin2_max = radius * radius / (dc * dc);
+            let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+            1.0 / (2.0 * PI * sin2_max / (1.0 + cos_max))
+        };
+        let r = (record.hit_point - hit.hit_point).norm();
+        Some(LightSample::unless_shadowed(
+            hit,
+            *wi,
+            r,
+            self.material.radiance(),
+            pdf,
+        ))
+    }
+
+    fn power(&self, _scene_radius: f64) -> f64 {
+        let radius = self.sphere.radius();
+        4.0 * PI * PI * radius * radius * luminance(&self.material.radiance())
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            bounds: self.sphere.get_bounding_box(),
+            w: Vec3::z(),
+            phi: self.power(0.0),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        })
+    }
+}
+
+// direction at angle acos(cos_theta) from w, turned by 2π·u about it
+pub fn cone_direction(w: &Vec3, cos_theta: f64, u: f64) -> Vec3 {
+    let (a, b) = plane_axes(w);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u;
+    (a * phi.cos() + b * phi.sin()) * sin_theta + w * cos_theta
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::light::tests::{hit, origin_ray, over_directions, world};
+
+    fn check(center: Point3<f64>, radius: f64) {
+        // scene units of half the normalised ones, centered on the origin
+        let sphere = Arc::new(Sphere::new(0, radius, center + Vec3::repeat(1.0), 2.0));
+        let world = world(vec![sphere.clone()]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, Vec3::z());
+        let light = SphereLight::new(sphere, Emissive::new(1.0, Color::repeat(1.0)));
+        let pdf = |wi: &Vec3| light.sample_direction(&hit, wi).unwrap().pdf;
+        let total = over_directions(pdf, 200);
+        assert!((total - 1.0).abs() < 0.01, "{}", total);
+        // sampled directions have the density sample_direction gives them
+        for s in light.sample(&hit, 100).iter().filter(|s| s.pdf > 0.0) {
+            assert!((pdf(&s.wi) - s.pdf).abs() < 1e-9 * s.pdf);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_cone() {
+        check(Point3::new(0.2, 0.5, 0.8), 0.5);
+        check(Point3::new(0.3, -0.2, 0.6), 0.4);
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_from_inside() {
+        check(Point3::new(0.1, 0.0, 0.0), 0.5);
+    }
+}
@@ -0,0 +1,162 @@
+use nalgebra::Point3;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::color::{luminance, Color};
+use crate::geometric_object::plane_axes;
+use crate::light::{point_sample, IesProfile, Intensity, Light, LightBounds, LightSample};
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+// A point light limited to a cone around `direction`. Full strength inside
+// `inner_angle`, fading smoothly to nothing at `outer_angle`, both measured
+// from the axis in radians. A measured profile is aimed with its nadir along
+// the axis and is cut off by the cone the same way
+pub struct SpotLight {
+    pub cl: Color,
+    pub location: Point3<f64>,
+    pub direction: Vec3,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    candela: f64,
+    solid_angle: f64,
+    profile: Option<Arc<IesProfile>>,
+}
+
+impl SpotLight {
+    pub fn new(
+        intensity: Intensity,
+        cl: Color,
+        location: Point3<f64>,
+        direction: Vec3,
+        inner_angle: f64,
+        outer_angle: f64,
+        profile: Option<Arc<IesProfile>>,
+    ) -> SpotLight {
+        let mut light = SpotLight {
+            cl,
+            location,
+            direction,
+            inner_angle,
+            outer_angle,
+            candela: 0.0,
+            solid_angle: 0.0,
+            profile,
+        };
+        light.solid_angle = light.integrate();
+        light.candela = intensity.candela(light.solid_angle);
+        light
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        let cos_outer = self.outer_angle.cos();
+        let cos_inner = self.inner_angle.cos();
+        if cos_theta >= cos_inner {
+            return 1.0;
+        }
+        let x = ((cos_theta - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        x * x * (3.0 - 2.0 * x) // smoothstep
+    }
+
+    // intensity towards wo relative to the axis
+    fn relative(&self, wo: &Vec3) -> f64 {
+        let axis = self.direction.normalize();
+        let profile = self.profile.as_ref().map_or(1.0, |p| p.relative(&axis, wo));
+        self.falloff(wo.dot(&axis)) * profile
+    }
+
+    // relative intensity over the cone, by the midpoint rule
+    fn integrate(&self) -> f64 {
+        let axis = self.direction.normalize();
+        let (a, b) = plane_axes(&axis);
+        let (n_theta, n_phi) = (90, 72);
+        let d_theta = self.outer_angle.clamp(0.0, PI) / n_theta as f64;
+        let d_phi = 2.0 * PI / n_phi as f64;
+        (0..n_theta)
+            .map(|i| {
+                let theta = (i as f64 + 0.5) * d_theta;
+                let ring: f64 = (0..n_phi)
+                    .map(|j| {
+                        let phi = (j as f64 + 0.5) * d_phi;
+                        let wo = (a * phi.cos() + b * phi.sin()) * theta.sin() + axis * theta.cos();
+                        self.relative(&wo)
+                    })
+                    .sum();
+                ring * theta.sin() * d_theta * d_phi
+            })
+            .sum()
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, hit: &RayHit, _n: usize) -> Vec<LightSample> {
+        let wo = (hit.hit_point - self.location).normalize();
+        let intensity = self.cl * (self.candela * self.relative(&wo));
+        vec![point_sample(hit, &self.location, intensity)]
+    }
+
+    fn power(&self, _scene_radius: f64) -> f64 {
+        self.solid_angle * self.candela * luminance(&self.cl)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            bounds: AABB::new(self.location, self.location),
+            w: self.direction.normalize(),
+            phi: self.power(0.0),
+            cos_theta_o: 1.0,
+            cos_theta_e: self.outer_angle.cos(),
+            two_sided: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot(inner_angle: f64, outer_angle: f64) -> SpotLight {
+        let location = Point3::origin();
+        let intensity = Intensity::Lumens(100.0);
+        SpotLight::new(
+            intensity,
+            Color::repeat(1.0),
+            location,
+            -Vec3::y(),
+            inner_angle,
+            outer_angle,
+            None,
+        )
+    }
+
+    #[test]
+    fn falloff_is_smooth_between_the_cones() {
+        let light = spot(0.2, 0.6);
+        assert_eq!(light.falloff(1.0), 1.0);
+        assert_eq!(light.falloff(0.2f64.cos()), 1.0);
+        assert_eq!(light.falloff(0.6f64.cos()), 0.0);
+        assert_eq!(light.falloff(0.0), 0.0);
+        let mut last = 1.0;
+        for i in 1..20 {
+            let theta = 0.2 + 0.4 * i as f64 / 20.0;
+            let f = light.falloff(theta.cos());
+            assert!(f > 0.0 && f < last, "{} at {}", f, theta);
+            last = f;
+        }
+    }
+
+    #[test]
+    fn lumens_are_spread_over_the_cone() {
+        // a hard edged cone covers 2π(1 - cos θ)
+        let light = spot(0.5, 0.5);
+        let solid_angle = 2.0 * PI * (1.0 - 0.5f64.cos());
+        assert!((light.solid_angle - solid_angle).abs() < 1e-3 * solid_angle);
+        assert!((light.power(0.0) - 100.0).abs() < 1e-9);
+
+        // a soft edge adds less than the outer cone would
+        let soft = spot(0.2, 0.5);
+        assert!(soft.solid_angle < light.solid_angle);
+        assert!(soft.candela > light.candela);
+    }
+}
@@ -0,0 +1,366 @@
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+
+use crate::color::{luminance, Color};
+use crate::geometric_object::{plane_axes, scale_direction};
+use crate::light::{Background, Light, LightSample};
+use crate::model::Vec3;
+use crate::ray::RayHit;
+
+// angular radius of the sun seen from the ground
+const SUN_RADIUS: f64 = 0.004_65;
+// luminance of the sun above the atmosphere, in kcd/m², the unit of the sky
+const SUN_LUMINANCE: f64 = 1.6e6;
+
+// Where and when the sun is seen. Latitude and longitude are in degrees,
+// north and east positive, and the hour is UTC
+pub struct SunPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: f64,
+}
+
+impl SunPosition {
+    // Unit vector towards the sun in scene units, y up, north along -z and
+    // east along +x. Follows the NOAA approximation of the solar position
+    pub fn direction(&self) -> Vec3 {
+        let days = if is_leap_year(self.year) {
+            366.0
+        } else {
+            365.0
+        };
+        let gamma = 2.0 * PI / days
+            * (day_of_year(self.year, self.month, self.day) - 1.0 + (self.hour - 12.0) / 24.0);
+        // equation of time in minutes and declination in radians
+        let eqtime = 229.18
+            * (0.000_075 + 0.001_868 * gamma.cos()
+                - 0.032_077 * gamma.sin()
+                - 0.014_615 * (2.0 * gamma).cos()
+                - 0.040_849 * (2.0 * gamma).sin());
+        let decl = 0.006_918 - 0.399_912 * gamma.cos() + 0.070_257 * gamma.sin()
+            - 0.006_758 * (2.0 * gamma).cos()
+            + 0.000_907 * (2.0 * gamma).sin()
+            - 0.002_697 * (3.0 * gamma).cos()
+            + 0.001_48 * (3.0 * gamma).sin();
+        let solar_minutes = self.hour * 60.0 + eqtime + 4.0 * self.longitude;
+        let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+        let lat = self.latitude.to_radians();
+        let cos_zenith =
+            (lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos()).clamp(-1.0, 1.0);
+        let sin_zenith = (1.0 - cos_zenith * cos_zenith).sqrt();
+        // clockwise from north
+        let azimuth = hour_angle
+            .sin()
+            .atan2(hour_angle.cos() * lat.sin() - decl.tan() * lat.cos())
+            + PI;
+        Vec3::new(
+            sin_zenith * azimuth.sin(),
+            cos_zenith,
+            -sin_zenith * azimuth.cos(),
+        )
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn day_of_year(year: i32, month: u32, day: u32) -> f64 {
+    const DAYS_BEFORE: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let month = month.clamp(1, 12);
+    let leap_day = u32::from(month > 2 && is_leap_year(year));
+    f64::from(DAYS_BEFORE[month as usize - 1] + day + leap_day)
+}
+
+// Preetham's analytic daylight: a sky dome from the Perez distribution of
+// luminance and chromaticity, and a sun disk dimmed by the air it passes
+// through. Turbidity runs from 2 for clear to 10 for hazy air, and radiance
+// is in kcd/m² times `intensity`. With the sun below the horizon the sky is
+// dark, and so is everything below the horizon
+pub struct SunSky {
+    sun: Vec3, // towards the sun in scene units
+    sun_radiance: Color,
+    zenith: [f64; 3], // x, y chromaticity and luminance Y at the zenith
+    perez: [[f64; 5]; 3],
+    perez_at_zenith: [f64; 3],
+    intensity: f64,
+    sample_points_sqrt: usize,
+}
+
+impl SunSky {
+    pub fn new(position: &SunPosition, turbidity: f64, intensity: f64) -> SunSky {
+        let t = turbidity.clamp(2.0, 10.0);
+        let sun = position.direction();
+        let theta_s = sun.y.clamp(-1.0, 1.0).acos();
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+            let ts = [t * t, t, 1.0];
+            (0..3)
+                .map(|i| ts[i] * (0..4).map(|j| m[i][j] * thetas[j]).sum::<f64>())
+                .sum::<f64>()
+        };
+        let zenith = [
+            chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+            zenith_luminance,
+        ];
+        let perez = [
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+        ];
+        let perez_at_zenith = [0, 1, 2].map(|i| perez_function(&perez[i], 1.0, theta_s.cos()));
+        SunSky {
+            sun,
+            sun_radiance: sun_transmittance(sun.y, t) * SUN_LUMINANCE,
+            zenith,
+            perez,
+            perez_at_zenith,
+            intensity,
+            sample_points_sqrt: 5,
+        }
+    }
+
+    fn sun_is_up(&self) -> bool {
+        self.sun.y > 0.0
+    }
+
+    // direction in scene units
+    fn sky(&self, d: &Vec3) -> Color {
+        if !self.sun_is_up() || d.y <= 0.0 {
+            return Color::zeros();
+        }
+        let cos_gamma = d.dot(&self.sun).clamp(-1.0, 1.0);
+        let [x, y, luminance] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez_function(&self.perez[i], d.y, cos_gamma)
+                / self.perez_at_zenith[i]
+        });
+        xyy_to_rgb(x, y, luminance.max(0.0)) * self.intensity
+    }
+
+    fn sun_disk(&self, d: &Vec3) -> Color {
+        if self.sun_is_up() && d.y > 0.0 && d.dot(&self.sun) >= SUN_RADIUS.cos() {
+            self.sun_radiance * self.intensity
+        } else {
+            Color::zeros()
+        }
+    }
+
+    // density of the sampling in `sample` at a direction in scene units
+    fn pdf(&self, d: &Vec3) -> f64 {
+        let cos_max = SUN_RADIUS.cos();
+        let cone_pdf = if d.dot(&self.sun) >= cos_max {
+            1.0 / (2.0 * PI * (1.0 - cos_max))
+        } else {
+            0.0
+        };
+        0.5 * d.y.max(0.0) / PI + 0.5 * cone_pdf
+    }
+
+    // light along a direction in scene units
+    fn sample_scene_direction(&self, hit: &RayHit, d: &Vec3) -> LightSample {
+        let pdf = self.pdf(d);
+        if pdf <= 0.0 {
+            return LightSample::missed(scale_direction(*d));
+        }
+        let radiance = self.sky(d) + self.sun_disk(d);
+        LightSample::unless_shadowed(hit, scale_direction(*d), f64::INFINITY, radiance, pdf)
+    }
+}
+
+// Perez et al. luminance distribution for a direction at cos_theta from the
+// zenith and cos_gamma from the sun
+fn perez_function(c: &[f64; 5], cos_theta: f64, cos_gamma: f64) -> f64 {
+    let gamma = cos_gamma.acos();
+    (1.0 + c[0] * (c[1] / cos_theta.max(0.01)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::zeros();
+    }
+    let cx = x / y * luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    Color::new(
+        3.2406 * cx - 1.5372 * luminance - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * luminance + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * luminance + 1.0570 * cz,
+    )
+    .map(|c| c.max(0.0))
+}
+
+// Rayleigh and aerosol extinction along the sun's path through the air, for
+// the red, green and blue wavelengths in micrometres
+fn sun_transmittance(cos_zenith: f64, turbidity: f64) -> Color {
+    if cos_zenith <= 0.0 {
+        return Color::zeros();
+    }
+    let zenith_degrees = cos_zenith.acos().to_degrees();
+    let air_mass = 1.0 / (cos_zenith + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+    let beta = 0.046_08 * turbidity - 0.045_86;
+    Color::new(0.65, 0.57, 0.475).map(|lambda: f64| {
+        let rayleigh = 0.008_735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-(rayleigh + aerosol) * air_mass).exp()
+    })
+}
+
+impl Background for SunSky {
+    fn radiance(&self, dir: &Vec3) -> Color {
+        let d = scale_direction(dir.normalize());
+        self.sky(&d) + self.sun_disk(&d)
+    }
+}
+
+impl Light for SunSky {
+    // Each sample goes to the sun's cone or, cosine weighted, to the sky with
+    // even odds, weighted by the density of drawing it either way
+    fn sample(&self, hit: &RayHit, n: usize) -> Vec<LightSample> {
+        if !self.sun_is_up() {
+            return vec![];
+        }
+        let mut rng = thread_rng();
+        let cos_max = SUN_RADIUS.cos();
+        let (a, b) = plane_axes(&self.sun);
+        (0..n)
+            .map(|_| {
+                let d = if rng.gen::<bool>() {
+                    let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_max);
+                    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                    let phi = 2.0 * PI * rng.gen::<f64>();
+                    (a * phi.cos() + b * phi.sin()) * sin_theta + self.sun * cos_theta
+                } else {
+                    let r = rng.gen::<f64>().sqrt();
+                    let phi = 2.0 * PI * rng.gen::<f64>();
+                    Vec3::new(r * phi.cos(), (1.0 - r * r).max(0.0).sqrt(), r * phi.sin())
+                };
+                self.sample_scene_direction(hit, &d)
+            })
+            .collect()
+    }
+
+    fn sample_count(&self) -> usize {
+        self.sample_points_sqrt * self.sample_points_sqrt
+    }
+
+    fn sample_direction(&self, hit: &RayHit, wi: &Vec3) -> Option<LightSample> {
+        if !self.sun_is_up() {
+            return Some(LightSample::missed(*wi));
+        }
+        Some(self.sample_scene_direction(hit, &scale_direction(wi.normalize())))
+    }
+
+    // the sun's disk and, roughly, the sky at its zenith over the hemisphere
+    fn power(&self, scene_radius: f64) -> f64 {
+        if !self.sun_is_up() {
+            return 0.0;
+        }
+        let sun_solid_angle = 2.0 * PI * (1.0 - SUN_RADIUS.cos());
+        let radiance =
+            luminance(&self.sun_radiance) * sun_solid_angle + self.zenith[2].max(0.0) * 2.0 * PI;
+        PI * scene_radius * scene_radius * radiance * self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::geometric_object::Sphere;
+    use crate::light::tests::{hit, origin_ray, world};
+
+    fn position(latitude: f64, month: u32, day: u32, hour: f64) -> SunPosition {
+        SunPosition {
+            latitude,
+            longitude: 0.0,
+            year: 2024,
+            month,
+            day,
+            hour,
+        }
+    }
+
+    #[test]
+    fn sun_follows_the_date_and_hour() {
+        assert_eq!(day_of_year(2024, 3, 1), 61.0);
+        assert_eq!(day_of_year(2023, 3, 1), 60.0);
+        // overhead at the equator at an equinox noon
+        assert!(position(0.0, 3, 20, 12.0).direction().y > 0.99);
+        // at the June solstice London sees it 62° up in the south
+        let london = position(51.5, 6, 21, 12.0).direction();
+        let elevation = london.y.asin().to_degrees();
+        assert!((elevation - 61.9).abs() < 0.5, "{}", elevation);
+        assert!(london.z > 0.0 && london.x.abs() < 0.05);
+        // east in the morning, set at midnight
+        assert!(position(0.0, 3, 20, 9.0).direction().x > 0.5);
+        assert!(position(51.5, 6, 21, 0.0).direction().y < 0.0);
+    }
+
+    #[test]
+    fn sky_is_brightest_around_the_sun() {
+        let sky = SunSky::new(&position(51.5, 9, 1, 15.0), 3.0, 1.0);
+        let away = Vec3::new(-sky.sun.x, sky.sun.y, -sky.sun.z);
+        let near = (sky.sun + Vec3::new(0.0, 0.05, 0.0)).normalize();
+        assert!(luminance(&sky.sky(&near)) > luminance(&sky.sky(&away)));
+        assert!(luminance(&sky.sun_disk(&sky.sun)) > luminance(&sky.sky(&near)));
+        assert_eq!(sky.sky(&-Vec3::y()), Color::zeros());
+        // nothing at night
+        let night = SunSky::new(&position(51.5, 9, 1, 0.0), 3.0, 1.0);
+        assert_eq!(night.radiance(&Vec3::y()), Color::zeros());
+        assert_eq!(night.power(1.0), 0.0);
+    }
+
+    #[test]
+    fn samples_have_the_density_of_their_direction() {
+        let sphere = Arc::new(Sphere::new(0, 1.0, Point3::origin(), 10.0));
+        let world = world(vec![sphere]);
+        let ray = origin_ray();
+        let hit = hit(&world, &ray, scale_direction(Vec3::y()));
+        let sky = SunSky::new(&position(51.5, 9, 1, 15.0), 3.0, 1.0);
+        let samples = sky.sample(&hit, 1000);
+        // about half go to the sun
+        let sun = samples
+            .iter()
+            .filter(|s| scale_direction(s.wi).dot(&sky.sun) >= SUN_RADIUS.cos())
+            .count();
+        assert!((400..600).contains(&sun), "{}", sun);
+        for s in &samples {
+            let pdf = sky.sample_direction(&hit, &s.wi).unwrap().pdf;
+            assert!((pdf - s.pdf).abs() < 1e-6 * s.pdf);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-#![allow(dead_code)]
 #![allow(
     clippy::cast_lossless,
     clippy::cast_possible_truncation,
@@ -17,33 +16,41 @@ use std::error::Error;
 use std::sync::Arc;
 use std::time::Instant;
 
-mod aabb;
-mod asset;
-mod brdf;
-mod camera;
-mod color;
-mod geometric_object;
-mod light;
-mod material;
-mod model;
-mod ray;
-mod sampler;
-mod view_plane;
-mod world;
-
-use crate::asset::Asset;
-use crate::camera::{Camera, CameraSetting, ThinLensCamera};
-use crate::color::to_rgb;
-use crate::geometric_object::BvhNode;
-use crate::light::{AmbientLight, AmbientOcculuder};
-use crate::model::Vec3;
-use crate::view_plane::ViewPlane;
-use crate::world::World;
+use raytracing::asset::Asset;
+use raytracing::camera::{Camera, CameraSetting, ThinLensCamera};
+use raytracing::color::to_rgb;
+use raytracing::light::{AmbientLight, AmbientOcculuder, LightSelection};
+use raytracing::material::{DirectLighting, Heuristic};
+use raytracing::model::Vec3;
+use raytracing::view_plane::ViewPlane;
+use raytracing::world::World;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let debug = env::args().any(|x| x == "--debug");
+    // --direct=light, brdf, balance or power to compare the estimators
+    let direct_lighting = env::args()
+        .find_map(|x| match x.strip_prefix("--direct=")? {
+            "light" => Some(DirectLighting::LightSampling),
+            "brdf" => Some(DirectLighting::BrdfSampling),
+            "balance" => Some(DirectLighting::Mis(Heuristic::Balance)),
+            "power" => Some(DirectLighting::Mis(Heuristic::Power)),
+            _ => None,
+        })
+        .unwrap_or(DirectLighting::Mis(Heuristic::Power));
+    // --lights=all, power or bvh to compare how lights are picked
+    let light_selection = env::args()
+        .find_map(|x| match x.strip_prefix("--lights=")? {
+            "all" => Some(LightSelection::All),
+            "power" => Some(LightSelection::Power),
+            "bvh" => Some(LightSelection::Bvh),
+            _ => None,
+        })
+        .unwrap_or(LightSelection::Bvh);
 
-    let asset = Asset::new("../assets/cornell_box.obj");
+    let asset = Asset::new("../assets/cornell_box.obj")?;
+    for warning in &asset.warnings {
+        eprintln!("warning: {}", warning);
+    }
 
     let ambient_light = AmbientLight {
         ls: 0.1,
@@ -67,14 +74,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         pixel_size: 1.0,
     };
 
-    let len = asset.geometries.len();
-    let world = World {
-        vp,
-        bvh: Arc::new(BvhNode::new(asset.geometries, 0, len)),
-        lights,
-        ambient_light,
-        materials: asset.materials,
-    };
+    let mut world = World::new(vp, asset.geometries, lights, ambient_light, asset.materials)?;
+    world.background = asset.background;
+    world.direct_lighting = direct_lighting;
+    world.light_selection = light_selection;
 
     let camera = ThinLensCamera {
         setting: CameraSetting::new(
@@ -1,23 +1,60 @@
-use crate::brdf::BRDF;
+use rand::{thread_rng, Rng};
+
+use crate::brdf::{GlossySpecular, Lambertian, PerfectSpecular, BRDF};
+use crate::btdf::BTDF;
 use crate::color::Color;
-use crate::light::Light;
+use crate::light::{Light, LightSample, LightSelection};
 use crate::model::Vec3;
-use crate::ray::{Ray, RayHit};
+use crate::ray::RayHit;
 
 pub mod emissive;
 pub mod matte;
 pub mod phong;
 pub mod reflective;
+pub mod transparent;
 
 pub use emissive::*;
 pub use matte::*;
 pub use phong::*;
 pub use reflective::*;
+pub use transparent::*;
+
+// How direct light is estimated. Multiple importance sampling splits each
+// light's samples between the light and the BRDF, weighted by Veach's balance
+// or power heuristic. Lights at a single point or from a single direction
+// are always sampled themselves
+#[derive(Clone, Copy)]
+pub enum DirectLighting {
+    LightSampling,
+    BrdfSampling,
+    Mis(Heuristic),
+}
+
+#[derive(Clone, Copy)]
+pub enum Heuristic {
+    Balance,
+    Power,
+}
+
+impl Heuristic {
+    // weight of a sample drawn by a strategy with density `own`, each
+    // density already multiplied by its strategy's number of samples
+    fn weight(self, own: f64, other: f64) -> f64 {
+        if own <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            Heuristic::Balance => own / (own + other),
+            Heuristic::Power => own * own / (own * own + other * other),
+        }
+    }
+}
 
 pub enum Material {
     Matte(Matte),
     Phong(Phong),
     Reflective(Reflective),
+    Transparent(Transparent),
     Emissive(Emissive),
 }
 
@@ -28,34 +65,144 @@ impl Material {
         }
 
         let ambient_color = self.ambient_color(hit);
-        hit.world
-            .lights
+        let wo = (hit.ray.dir * -1.0).normalize();
+        let world = hit.world;
+        let picked = match world.light_selection {
+            LightSelection::All => None,
+            LightSelection::Power => Some(world.light_sampler().sample_power(&mut thread_rng())),
+            LightSelection::Bvh => Some(world.light_sampler().sample_bvh(
+                &hit.hit_point,
+                &hit.normal,
+                &mut thread_rng(),
+            )),
+        };
+        let direct_color = match picked {
+            None => world
+                .lights()
+                .iter()
+                .map(|light| self.direct_color(hit, &wo, light.as_ref()))
+                .fold(Color::zeros(), |a, b| a + b),
+            // one light stands in for all of them, divided by the odds of picking it
+            Some(picked) => picked.map_or(Color::zeros(), |(i, pmf)| {
+                self.direct_color(hit, &wo, world.lights()[i].as_ref()) / pmf
+            }),
+        };
+        ambient_color
+            + direct_color
+            + self.reflective_color(hit, &wo)
+            + self.transmitted_color(hit, &wo)
+    }
+
+    // Light from one light reflected towards wo, averaging f·L·cosθ/pdf over
+    // the samples of each strategy in use
+    fn direct_color(&self, hit: &RayHit, wo: &Vec3, light: &(dyn Light + Send + Sync)) -> Color {
+        let strategy = hit.world.direct_lighting;
+        let budget = light.sample_count();
+        let (n_light, n_brdf) = match strategy {
+            DirectLighting::LightSampling => (budget, 0),
+            DirectLighting::BrdfSampling => (0, budget),
+            DirectLighting::Mis(_) => (budget - budget / 2, budget / 2),
+        };
+        let brdf_samples = self
+            .sample_brdf(hit, wo, n_brdf)
             .iter()
-            .map(|light| {
-                // wi: incoming direction
-                // ndotwi: angle between light and normal
-                let wi = light.get_direction(hit);
-                let ndotwi = hit.normal.dot(&wi);
-                // not hit by light
-                if ndotwi <= 0.0 {
-                    return Color::zeros();
-                }
+            .map(|wi| light.sample_direction(hit, wi))
+            .collect::<Option<Vec<LightSample>>>()
+            .unwrap_or_default();
+        // lights no direction finds and materials without a lobe leave the
+        // whole budget to the light
+        let n_light = if brdf_samples.is_empty() {
+            budget
+        } else {
+            n_light
+        };
+        let light_samples = if n_light > 0 {
+            light.sample(hit, n_light)
+        } else {
+            vec![]
+        };
+        let n_light = light_samples.len() as f64;
+        let n_brdf = brdf_samples.len() as f64;
+        let weight = |own: f64, other: f64| match strategy {
+            DirectLighting::Mis(heuristic) => heuristic.weight(own, other),
+            _ => 1.0,
+        };
+        let from_light = light_samples
+            .iter()
+            .map(|sample| {
+                let brdf_pdf = if n_brdf > 0.0 {
+                    self.brdf_pdf(hit, wo, &sample.wi)
+                } else {
+                    0.0
+                };
+                self.reflected(hit, wo, sample, sample.pdf)
+                    * weight(n_light * sample.pdf, n_brdf * brdf_pdf)
+                    / n_light
+            })
+            .fold(Color::zeros(), |a, b| a + b);
+        let from_brdf = brdf_samples
+            .iter()
+            .map(|sample| {
+                let brdf_pdf = self.brdf_pdf(hit, wo, &sample.wi);
+                self.reflected(hit, wo, sample, brdf_pdf)
+                    * weight(n_brdf * brdf_pdf, n_light * sample.pdf)
+                    / n_brdf
+            })
+            .fold(Color::zeros(), |a, b| a + b);
+        from_light + from_brdf
+    }
 
-                let radiance = light.radiance(hit);
-                if radiance <= Vec3::zeros() {
-                    return Color::zeros();
-                }
+    // f·L·cosθ/pdf for one sample, nothing from below the surface
+    fn reflected(&self, hit: &RayHit, wo: &Vec3, sample: &LightSample, pdf: f64) -> Color {
+        // ndotwi: angle between light and normal
+        let ndotwi = hit.normal.dot(&sample.wi);
+        if ndotwi <= 0.0 || pdf <= 0.0 || sample.radiance <= Color::zeros() {
+            return Color::zeros();
+        }
+        (self.diffuse_color(hit, wo, &sample.wi) + self.specular_color(hit, wo, &sample.wi))
+            .component_mul(&sample.radiance)
+            * (ndotwi / pdf)
+    }
 
-                // wo: reflected direction
-                let shadow_amount = light.shadow_amount(hit);
+    fn brdf_lobes(&self) -> Lobes<'_> {
+        match self {
+            Material::Matte(m) => Lobes {
+                diffuse: Some((&m.diffuse_brdf, 1.0)),
+                glossy: None,
+            },
+            Material::Phong(m) => lobe_odds(&m.diffuse_brdf, &m.specular_brdf),
+            Material::Reflective(m) => lobe_odds(&m.diffuse_brdf, &m.specular_brdf),
+            Material::Transparent(m) => lobe_odds(&m.diffuse_brdf, &m.specular_brdf),
+            Material::Emissive(_) => Lobes {
+                diffuse: None,
+                glossy: None,
+            },
+        }
+    }
 
-                let wo = (hit.ray.dir * -1.0).normalize();
-                (self.diffuse_color(hit, &wo, &wi) + self.specular_color(hit, &wo, &wi))
-                    .component_mul(&(radiance * shadow_amount))
-                    * ndotwi
-                    + self.reflective_color(hit, &wo)
+    fn sample_brdf(&self, hit: &RayHit, wo: &Vec3, n: usize) -> Vec<Vec3> {
+        let Lobes { diffuse, glossy } = self.brdf_lobes();
+        if diffuse.is_none() && glossy.is_none() {
+            return vec![];
+        }
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| match (diffuse, glossy) {
+                (Some((d, odds)), _) if glossy.is_none() || rng.gen::<f64>() < odds => {
+                    d.sample_wi(hit, rng.gen(), rng.gen())
+                }
+                (_, Some((g, _))) => g.sample_wi(hit, wo, rng.gen(), rng.gen()),
+                // sample_brdf returned early without either lobe
+                _ => unreachable!(),
             })
-            .fold(ambient_color, |a, b| a + b)
+            .collect()
+    }
+
+    // density of sample_brdf at wi
+    fn brdf_pdf(&self, hit: &RayHit, wo: &Vec3, wi: &Vec3) -> f64 {
+        let Lobes { diffuse, glossy } = self.brdf_lobes();
+        diffuse.map_or(0.0, |(d, odds)| odds * d.pdf(hit, wi))
+            + glossy.map_or(0.0, |(g, odds)| odds * g.pdf(hit, wo, wi))
     }
 
     fn ambient_color(&self, hit: &RayHit) -> Color {
@@ -63,9 +210,11 @@ impl Material {
             Material::Matte(m) => m.diffuse_brdf.rho(),
             Material::Phong(m) => m.ambient_brdf.rho(),
             Material::Reflective(m) => m.ambient_brdf.rho(),
+            Material::Transparent(m) => m.ambient_brdf.rho(),
             Material::Emissive(_) => Color::zeros(),
         };
-        rho.component_mul(&hit.world.ambient_light.radiance(hit))
+        let rho = hit.vertex_color.map_or(rho, |c| rho.component_mul(&c));
+        rho.component_mul(&hit.world.ambient_light.radiance())
     }
 
     fn diffuse_color(&self, hit: &RayHit, wo: &Vec3, wi: &Vec3) -> Color {
@@ -74,6 +223,7 @@ impl Material {
             Material::Matte(m) => m.diffuse_brdf.f(hit, &z, &z),
             Material::Phong(m) => m.diffuse_brdf.f(hit, wo, wi),
             Material::Reflective(m) => m.diffuse_brdf.f(hit, wo, wi),
+            Material::Transparent(m) => m.diffuse_brdf.f(hit, wo, wi),
             Material::Emissive(_) => z,
         }
     }
@@ -83,23 +233,137 @@ impl Material {
             Material::Matte(_) | Material::Emissive(_) => Color::zeros(),
             Material::Phong(m) => m.specular_brdf.f(hit, wo, wi),
             Material::Reflective(m) => m.specular_brdf.f(hit, wo, wi),
+            Material::Transparent(m) => m.specular_brdf.f(hit, wo, wi),
         }
     }
 
     fn reflective_color(&self, hit: &RayHit, wo: &Vec3) -> Color {
         match self {
             Material::Matte(_) | Material::Phong(_) | Material::Emissive(_) => Color::zeros(),
-            Material::Reflective(m) => {
-                let normal = hit.normal;
-                let ndotwo = normal.dot(wo);
-                let wi = normal * (2.0 * ndotwo) - wo;
-                let fr = m.reflective_brdf.sample_f(hit, wo, &wi);
-                let reflected_ray = Ray::new(hit.hit_point, wi);
+            Material::Reflective(m) => reflect(&m.reflective_brdf, hit, wo),
+            Material::Transparent(m) => {
+                if m.specular_btdf.tir(hit) {
+                    // all light is reflected
+                    let wi = mirror(hit, wo);
+                    let reflected_ray = hit.spawn_ray(wi);
+                    hit.world.trace(&reflected_ray, hit.depth + 1)
+                } else {
+                    reflect(&m.reflective_brdf, hit, wo)
+                }
+            }
+        }
+    }
+
+    fn transmitted_color(&self, hit: &RayHit, wo: &Vec3) -> Color {
+        match self {
+            Material::Transparent(m) if !m.specular_btdf.tir(hit) => {
+                let (wt, ft) = m.specular_btdf.sample_f(hit, wo);
+                let transmitted_ray = hit.spawn_ray(wt);
                 hit.world
-                    .trace(&reflected_ray, hit.depth + 1)
-                    .component_mul(&fr)
-                    * normal.dot(&wi)
+                    .trace(&transmitted_ray, hit.depth + 1)
+                    .component_mul(&ft)
+                    * hit.normal.dot(&wt).abs()
+            }
+            _ => Color::zeros(),
+        }
+    }
+}
+
+// mirror direction of wo about the normal
+fn mirror(hit: &RayHit, wo: &Vec3) -> Vec3 {
+    hit.normal * (2.0 * hit.normal.dot(wo)) - wo
+}
+
+fn reflect(brdf: &PerfectSpecular, hit: &RayHit, wo: &Vec3) -> Color {
+    let wi = mirror(hit, wo);
+    let fr = brdf.sample_f(hit, wo, &wi);
+    let reflected_ray = hit.spawn_ray(wi);
+    hit.world
+        .trace(&reflected_ray, hit.depth + 1)
+        .component_mul(&fr)
+        * hit.normal.dot(&wi)
+}
+
+// the lobes direct light is sampled from, each with the odds of picking it
+struct Lobes<'a> {
+    diffuse: Option<(&'a Lambertian, f64)>,
+    glossy: Option<(&'a GlossySpecular, f64)>,
+}
+
+// odds in proportion to how much each lobe reflects, leaving out lobes that
+// reflect nothing
+fn lobe_odds<'a>(diffuse: &'a Lambertian, glossy: &'a GlossySpecular) -> Lobes<'a> {
+    let kd = diffuse.rho().max().max(0.0);
+    let ks = glossy.ks.max(0.0);
+    if kd + ks <= 0.0 {
+        return Lobes {
+            diffuse: None,
+            glossy: None,
+        };
+    }
+    Lobes {
+        diffuse: Some((diffuse, kd / (kd + ks))).filter(|_| kd > 0.0),
+        glossy: Some((glossy, ks / (kd + ks))).filter(|_| ks > 0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::geometric_object::Sphere;
+    use crate::light::tests::{hit, world};
+    use crate::light::{Intensity, PointLight};
+    use crate::ray::Ray;
+
+    fn mirror_material() -> Material {
+        Material::Reflective(Reflective::new(
+            Lambertian::new(0.0, Color::zeros()),
+            Lambertian::new(0.0, Color::zeros()),
+            GlossySpecular::new(0.0, 1.0),
+            PerfectSpecular::new(0.5, Color::repeat(1.0)),
+        ))
+    }
+
+    #[test]
+    fn reflection_is_added_once_whatever_the_lights() {
+        // an emitter straight above a mirror facing +z, at (0, 0, 3) once scaled
+        let emitter = Sphere::new(0, 1.0, Point3::new(1.0, 1.0, 4.0), 2.0);
+        let mut world = world(vec![Arc::new(emitter)]);
+        let ray = Ray::new(Point3::origin(), -Vec3::z());
+        let mirror = mirror_material();
+        let unlit = mirror.shade(&hit(&world, &ray, Vec3::z()));
+        assert!((unlit - Color::repeat(0.5)).norm() < 1e-9);
+
+        // lights behind the mirror add nothing but must not repeat the reflection
+        let below = |z| -> Arc<dyn Light + Send + Sync> {
+            Arc::new(PointLight::new(
+                Intensity::Candela(1.0),
+                Color::repeat(1.0),
+                Point3::new(1.0, 1.0, z),
+                None,
+            ))
+        };
+        world.set_lights(vec![below(-1.0), below(-2.0)]);
+        let lit = mirror.shade(&hit(&world, &ray, Vec3::z()));
+        assert!((lit - unlit).norm() < 1e-9);
+    }
+
+    #[test]
+    fn heuristic_weights_sum_to_one() {
+        for heuristic in [Heuristic::Balance, Heuristic::Power] {
+            for (a, b) in [(1.0, 1.0), (0.3, 2.0), (5.0, 1e-3), (1.0, 0.0)] {
+                let sum = heuristic.weight(a, b) + heuristic.weight(b, a);
+                assert!((sum - 1.0).abs() < 1e-12);
             }
+            // a strategy that can't draw the sample gets none of it
+            assert_eq!(heuristic.weight(0.0, 1.0), 0.0);
+            assert_eq!(heuristic.weight(0.0, 0.0), 0.0);
+            assert_eq!(heuristic.weight(1.0, 0.0), 1.0);
         }
+        assert_eq!(Heuristic::Balance.weight(3.0, 1.0), 0.75);
+        assert_eq!(Heuristic::Power.weight(3.0, 1.0), 0.9);
     }
 }
@@ -0,0 +1,28 @@
+use crate::brdf::{GlossySpecular, Lambertian, PerfectSpecular};
+use crate::btdf::PerfectTransmitter;
+
+pub struct Transparent {
+    pub ambient_brdf: Lambertian,
+    pub diffuse_brdf: Lambertian,
+    pub specular_brdf: GlossySpecular,
+    pub reflective_brdf: PerfectSpecular,
+    pub specular_btdf: PerfectTransmitter,
+}
+
+impl Transparent {
+    pub fn new(
+        ambient_brdf: Lambertian,
+        diffuse_brdf: Lambertian,
+        specular_brdf: GlossySpecular,
+        reflective_brdf: PerfectSpecular,
+        specular_btdf: PerfectTransmitter,
+    ) -> Transparent {
+        Transparent {
+            ambient_brdf,
+            diffuse_brdf,
+            specular_brdf,
+            reflective_brdf,
+            specular_btdf,
+        }
+    }
+}
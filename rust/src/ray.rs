@@ -1,5 +1,6 @@
-use nalgebra::Point3;
+use nalgebra::{Matrix4, Point2, Point3};
 
+use crate::color::Color;
 use crate::model::Vec3;
 use crate::world::World;
 
@@ -21,7 +22,11 @@ impl Ray {
 pub struct HitRecord {
     pub dist: f64,
     pub hit_point: Point3<f64>,
-    pub normal: Vec3,
+    pub normal: Vec3,           // shading normal
+    pub geometric_normal: Vec3, // of the surface itself, used to leave it
+    pub error: Vec3,            // bound on the rounding error of hit_point
+    pub uv: Point2<f64>,
+    pub vertex_color: Option<Color>,
     pub material_id: usize,
 }
 
@@ -29,7 +34,130 @@ pub struct RayHit<'a> {
     pub ray: &'a Ray,
     pub hit_point: Point3<f64>,
     pub normal: Vec3,
+    pub offset: Vec3, // moves hit_point clear of the surface, see spawn_ray
+    pub inside: bool, // the ray hit the back of the surface
     pub world: &'a World,
     pub depth: i32,
     pub material_id: usize,
+    pub vertex_color: Option<Color>,
+}
+
+impl<'a> RayHit<'a> {
+    // Secondary ray from the hit point. The origin is pushed along the
+    // geometric normal past the error bounds of the hit point, on the side
+    // the ray leaves towards, so it can't hit the same surface again
+    pub fn spawn_ray(&self, dir: Vec3) -> Ray {
+        let offset = if self.offset.dot(&dir) < 0.0 {
+            -self.offset
+        } else {
+            self.offset
+        };
+        let mut origin = self.hit_point + offset;
+        for i in 0..3 {
+            if offset[i] > 0.0 {
+                origin[i] = next_float_up(origin[i]);
+            } else if offset[i] < 0.0 {
+                origin[i] = next_float_down(origin[i]);
+            }
+        }
+        Ray::new(origin, dir)
+    }
+}
+
+// Floating point error bounds after pbrt, section 3.9
+// https://pbr-book.org/3ed-2018/Shapes/Managing_Rounding_Error
+
+// bound on the relative error of n rounded operations
+pub fn gamma(n: u32) -> f64 {
+    let e = n as f64 * f64::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
+// error of a point computed from p with n operations
+pub fn error_bound(p: &Point3<f64>, n: u32) -> Vec3 {
+    p.coords.abs() * gamma(n)
+}
+
+// error of p carried through an affine transform
+pub fn transform_error(m: &Matrix4<f64>, p: &Point3<f64>, error: &Vec3) -> Vec3 {
+    let linear = m.fixed_slice::<3, 3>(0, 0).abs();
+    let translation = m.fixed_slice::<3, 1>(0, 3).abs();
+    (linear * p.coords.abs() + translation) * gamma(3) + linear * error * (1.0 + gamma(3))
+}
+
+// distance along the normal that clears an error box
+pub fn offset_along(normal: &Vec3, error: &Vec3) -> Vec3 {
+    normal * normal.abs().dot(error)
+}
+
+pub fn next_float_up(x: f64) -> f64 {
+    if x.is_infinite() && x > 0.0 {
+        return x;
+    }
+    // -0.0 and 0.0 both step to the smallest positive value
+    let x = if x == 0.0 { 0.0 } else { x };
+    let bits = x.to_bits();
+    f64::from_bits(if x >= 0.0 { bits + 1 } else { bits - 1 })
+}
+
+fn next_float_down(x: f64) -> f64 {
+    -next_float_up(-x)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{thread_rng, Rng};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::geometric_object::{Geometry, Sphere};
+    use crate::light::tests::world;
+
+    #[test]
+    fn next_float_steps_one_ulp() {
+        assert_eq!(next_float_up(1.0), 1.0 + f64::EPSILON);
+        assert_eq!(next_float_down(1.0), 1.0 - f64::EPSILON / 2.0);
+        let smallest = f64::from_bits(1);
+        assert_eq!(next_float_up(-0.0), smallest);
+        assert_eq!(next_float_up(0.0), smallest);
+        assert_eq!(next_float_down(0.0), -smallest);
+        assert_eq!(next_float_up(-smallest), 0.0);
+        assert_eq!(next_float_up(f64::INFINITY), f64::INFINITY);
+        assert_eq!(next_float_down(f64::NEG_INFINITY), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn spawned_rays_clear_the_surface() {
+        // small spheres far from the origin have the largest error relative to their size
+        let sphere = Arc::new(Sphere::new(0, 1e-3, Point3::new(-500.0, 300.0, 2e3), 2.0));
+        let world = world(vec![sphere.clone()]);
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let target = sphere.get_center() + Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 1e-3;
+            let origin = target + Vec3::new(rng.gen(), rng.gen(), -1.0) * 0.1;
+            let ray = Ray::new(origin, target - origin);
+            let record = match sphere.intersects(&ray, 0.0, f64::INFINITY) {
+                Some(record) => record,
+                None => continue,
+            };
+            let hit = RayHit {
+                ray: &ray,
+                hit_point: record.hit_point,
+                normal: record.normal,
+                offset: offset_along(&record.geometric_normal, &record.error),
+                inside: false,
+                world: &world,
+                depth: 0,
+                material_id: 0,
+                vertex_color: None,
+            };
+            let n = record.geometric_normal;
+            let outwards = hit.spawn_ray(n + Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 0.5);
+            assert!(sphere.intersects(&outwards, 0.0, f64::INFINITY).is_none());
+            // inwards the ray only finds the far side
+            let inwards = hit.spawn_ray(-n);
+            let far = sphere.intersects(&inwards, 0.0, f64::INFINITY).unwrap();
+            assert!(far.dist > sphere.radius());
+        }
+    }
 }
@@ -3,7 +3,6 @@ use rand::distributions::Standard;
 use rand::{thread_rng, Rng};
 use std::f64::consts::FRAC_PI_4;
 
-use crate::geometric_object::Triangle;
 use crate::model::Vec3;
 
 pub fn get_square_sampler(n: usize) -> impl Iterator<Item = Point2<f64>> {
@@ -13,8 +12,10 @@ pub fn get_square_sampler(n: usize) -> impl Iterator<Item = Point2<f64>> {
         .map(|(i, j)| Point2::new(i, j))
 }
 
-pub fn get_triangle_sampler(n: usize, t: &Triangle) -> impl Iterator<Item = Point3<f64>> {
-    let (x, y, z) = (t.x, t.y, t.z);
+pub fn get_triangle_sampler(
+    n: usize,
+    [x, y, z]: [Point3<f64>; 3],
+) -> impl Iterator<Item = Point3<f64>> {
     get_square_sampler(n).map(move |p| {
         let mut a = p.x;
         let mut b = p.y;
@@ -27,15 +28,18 @@ pub fn get_triangle_sampler(n: usize, t: &Triangle) -> impl Iterator<Item = Poin
 }
 
 pub fn get_hemisphere_sampler(n: usize) -> impl Iterator<Item = Vec3> {
-    get_square_sampler(n).map(|p| {
-        let e = 1.0;
-        let phi = 2.0 * std::f64::consts::PI * p.x;
-        let cos_phi = phi.cos();
-        let sin_phi = phi.sin();
-        let cos_theta = (1.0 - p.y).powf((e + 1.0_f64).recip());
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-        Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)
-    })
+    get_square_sampler(n).map(|p| hemisphere_direction(p.x, p.y))
+}
+
+// cosine weighted direction about +z for a point of the unit square
+pub fn hemisphere_direction(u: f64, v: f64) -> Vec3 {
+    let e = 1.0;
+    let phi = 2.0 * std::f64::consts::PI * u;
+    let cos_phi = phi.cos();
+    let sin_phi = phi.sin();
+    let cos_theta = (1.0 - v).powf((e + 1.0_f64).recip());
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)
 }
 
 pub fn get_disk_sampler(n: usize) -> impl Iterator<Item = (Point2<f64>, Point2<f64>)> {
@@ -57,3 +61,43 @@ pub fn get_disk_sampler(n: usize) -> impl Iterator<Item = (Point2<f64>, Point2<f
         (p, Point2::new(r * phi_.cos(), r * phi_.sin()))
     })
 }
+
+// Discrete distribution proportional to non-negative weights, sampled by a
+// binary search over the running sum
+pub struct Distribution {
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution {
+    pub fn new(weights: &[f64]) -> Distribution {
+        let cdf: Vec<f64> = weights
+            .iter()
+            .scan(0.0, |sum, w| {
+                *sum += w.max(0.0);
+                Some(*sum)
+            })
+            .collect();
+        let total = cdf.last().copied().unwrap_or(0.0);
+        Distribution { cdf, total }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    // index for u in [0, 1), None when every weight is zero
+    pub fn sample(&self, u: f64) -> Option<usize> {
+        if self.total <= 0.0 {
+            return None;
+        }
+        let target = u * self.total;
+        let i = self.cdf.partition_point(|c| *c <= target);
+        Some(i.min(self.cdf.len() - 1))
+    }
+
+    pub fn pdf(&self, i: usize) -> f64 {
+        let previous = if i == 0 { 0.0 } else { self.cdf[i - 1] };
+        (self.cdf[i] - previous) / self.total
+    }
+}
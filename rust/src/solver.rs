@@ -0,0 +1,228 @@
+// Real roots of polynomials with coefficients ordered from the constant term
+// up, after Jochen Schwarze's solvers in Graphics Gems I
+use std::f64::consts::PI;
+
+// Near repeated roots the closed forms lose accuracy, an error of δ in a
+// discriminant moves a double root by √δ and the resolvent's root of a
+// quartic carries that on. So rather than the rounding of the last few
+// operations, a value within this fraction of the magnitudes of the terms it
+// comes from counts as zero, which merges roots closer than about √TOLERANCE
+// of their size
+const TOLERANCE: f64 = 1e-9;
+
+fn is_zero(x: f64, terms: f64) -> bool {
+    x.abs() <= TOLERANCE * terms
+}
+
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    if c[2] == 0.0 {
+        return if c[1] == 0.0 {
+            vec![]
+        } else {
+            vec![-c[0] / c[1]]
+        };
+    }
+    // x^2 + 2px + q = 0
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+    if is_zero(d, p * p + q.abs()) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    // x^3 + Ax^2 + Bx + C = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    // substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let p_terms = (sq_a / 3.0 + b.abs()) / 3.0;
+    let q_terms = (2.0 / 27.0 * sq_a * a.abs() + (a * b).abs() / 3.0 + c.abs()) / 2.0;
+
+    let roots = if is_zero(d, q_terms * q_terms + p_terms * p_terms * p_terms) {
+        if is_zero(q, q_terms) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // three real solutions
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+pub fn solve_quartic(coeffs: [f64; 5]) -> Vec<f64> {
+    // x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = coeffs[3] / coeffs[4];
+    let b = coeffs[2] / coeffs[4];
+    let c = coeffs[1] / coeffs[4];
+    let d = coeffs[0] / coeffs[4];
+
+    // substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+    let r_terms = 3.0 / 256.0 * sq_a * sq_a + sq_a * b.abs() / 16.0 + (a * c).abs() / 4.0 + d.abs();
+
+    let roots = if is_zero(r, r_terms) {
+        // no absolute term: y(y^3 + py + q) = 0
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // solve the resolvent cubic and take its one real solution to
+        // build two quadric equations
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u, z * z + r.abs()) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if is_zero(v, 2.0 * z.abs() + p.abs()) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.0]);
+        roots.extend(solve_quadratic([z + u, -v, 1.0]));
+        roots
+    };
+    // the closed form loses precision, polish the roots on the original polynomial
+    roots
+        .into_iter()
+        .map(|y| polish(&coeffs, y - a / 4.0))
+        .collect()
+}
+
+// Newton steps, kept only while they bring the polynomial closer to zero
+fn polish(coeffs: &[f64; 5], mut x: f64) -> f64 {
+    let f = |x: f64| coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c);
+    let df = |x: f64| {
+        coeffs[1..]
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(0.0, |acc, (i, c)| acc * x + (i + 1) as f64 * c)
+    };
+    for _ in 0..2 {
+        let next = x - f(x) / df(x);
+        if !next.is_finite() || f(next).abs() >= f(x).abs() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // distinct roots in increasing order, repeated roots may come back more than once
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        let mut roots = roots;
+        roots.sort_by(f64::total_cmp);
+        roots.dedup_by(|a, b| (*a - *b).abs() <= 1e-6 * a.abs().max(1.0));
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (r, e) in roots.iter().zip(expected) {
+            assert!((r - e).abs() <= 1e-6 * e.abs().max(1.0), "{:?}", roots);
+        }
+    }
+
+    // coefficients of the product of (x - root), constant term first
+    fn expand(roots: &[f64]) -> Vec<f64> {
+        roots.iter().fold(vec![1.0], |c, root| {
+            let mut next = vec![0.0; c.len() + 1];
+            for (i, ci) in c.iter().enumerate() {
+                next[i + 1] += ci;
+                next[i] -= ci * root;
+            }
+            next
+        })
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(solve_quadratic([-6.0, 1.0, 1.0]), &[-3.0, 2.0]);
+        assert_roots(solve_quadratic([1.0, 0.0, 1.0]), &[]);
+        assert_roots(solve_quadratic([-4.0, 2.0, 0.0]), &[2.0]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        for roots in [[-2.0, 1.0, 3.0], [-1e-3, 2e-3, 5e-3], [-700.0, 0.0, 200.0]] {
+            let c = expand(&roots);
+            assert_roots(solve_cubic([c[0], c[1], c[2], c[3]]), &roots);
+        }
+        // x^3 - x^2 + x - 1 = (x - 1)(x^2 + 1)
+        assert_roots(solve_cubic([-1.0, 1.0, -1.0, 1.0]), &[1.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        for roots in [
+            [-2.0, -0.5, 1.0, 3.0],
+            [-3e-3, -1e-3, 1e-3, 4e-3],
+            [-800.0, -10.0, 30.0, 500.0],
+        ] {
+            let c = expand(&roots);
+            assert_roots(solve_quartic([c[0], c[1], c[2], c[3], c[4]]), &roots);
+        }
+        // (x^2 + 1)(x^2 + 4)
+        assert_roots(solve_quartic([4.0, 0.0, 5.0, 0.0, 1.0]), &[]);
+        // (x - 1)(x + 1)(x^2 + 1)
+        assert_roots(solve_quartic([-1.0, 0.0, 0.0, 0.0, 1.0]), &[-1.0, 1.0]);
+    }
+
+    #[test]
+    fn repeated_roots_are_found_at_any_scale() {
+        for s in [1.0, 1e-3, 1e3] {
+            let c = expand(&[s, s]);
+            assert_roots(solve_quadratic([c[0], c[1], c[2]]), &[s]);
+            let c = expand(&[s, s, -2.0 * s]);
+            assert_roots(solve_cubic([c[0], c[1], c[2], c[3]]), &[-2.0 * s, s]);
+            let c = expand(&[s, s, s]);
+            assert_roots(solve_cubic([c[0], c[1], c[2], c[3]]), &[s]);
+            for other in [2.0 * s, 3.0 * s] {
+                let c = expand(&[s, s, other, other]);
+                assert_roots(solve_quartic([c[0], c[1], c[2], c[3], c[4]]), &[s, other]);
+            }
+            let c = expand(&[s, s, -s, 3.0 * s]);
+            assert_roots(
+                solve_quartic([c[0], c[1], c[2], c[3], c[4]]),
+                &[-s, s, 3.0 * s],
+            );
+        }
+    }
+}
@@ -1,58 +1,243 @@
-use nalgebra::Point3;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::{collections::HashMap, f64::INFINITY};
 
 use crate::color::Color;
-use crate::geometric_object::Geometry;
-use crate::light::{AmbientLight, Light};
-use crate::material::Material;
-use crate::model::Vec3;
-use crate::ray::{Ray, RayHit};
+use crate::error::SceneError;
+use crate::geometric_object::{BvhNode, Geometry};
+use crate::light::{AmbientLight, Background, Light, LightSampler, LightSelection};
+use crate::material::{DirectLighting, Heuristic, Material};
+use crate::ray::{offset_along, HitRecord, Ray, RayHit};
 use crate::view_plane::ViewPlane;
 
 pub struct World {
     pub vp: ViewPlane,
-    pub lights: Vec<Arc<dyn Light + Send + Sync>>,
-    pub bvh: Arc<dyn Geometry + Send + Sync>,
+    lights: Vec<Arc<dyn Light + Send + Sync>>, // see set_lights
+    pub bvh: Option<Arc<dyn Geometry + Send + Sync>>,
+    pub planes: Vec<Arc<dyn Geometry + Send + Sync>>, // unbounded, kept out of the BVH
     pub ambient_light: AmbientLight,
     pub materials: HashMap<usize, Box<Material>>,
+    pub background: Option<Arc<dyn Background + Send + Sync>>, // seen by rays that miss
+    pub direct_lighting: DirectLighting,
+    pub light_selection: LightSelection,
+    light_sampler: LightSampler, // over `lights`, kept in step with them and the geometry
 }
 
 impl World {
+    pub fn new(
+        vp: ViewPlane,
+        geometries: Vec<Arc<dyn Geometry + Send + Sync>>,
+        lights: Vec<Arc<dyn Light + Send + Sync>>,
+        ambient_light: AmbientLight,
+        materials: HashMap<usize, Box<Material>>,
+    ) -> Result<World, SceneError> {
+        let mut world = World {
+            vp,
+            bvh: None,
+            planes: vec![],
+            lights,
+            ambient_light,
+            materials,
+            background: None,
+            direct_lighting: DirectLighting::Mis(Heuristic::Power),
+            light_selection: LightSelection::Bvh,
+            light_sampler: LightSampler::new(&[], 0.0),
+        };
+        world.rebuild(geometries)?;
+        Ok(world)
+    }
+
+    // Meshes keep their own bottom level BVH, moving an instance only rebuilds
+    // the top level over the placed objects
+    pub fn rebuild(
+        &mut self,
+        geometries: Vec<Arc<dyn Geometry + Send + Sync>>,
+    ) -> Result<(), SceneError> {
+        if geometries.is_empty() {
+            return Err(SceneError::DegenerateGeometry {
+                file: None,
+                message: "the scene has no geometry".to_owned(),
+            });
+        }
+        if let Some(id) = geometries
+            .iter()
+            .flat_map(|g| g.material_ids())
+            .find(|id| !self.materials.contains_key(id))
+        {
+            return Err(SceneError::UnknownMaterial {
+                file: None,
+                line: None,
+                material: id.to_string(),
+            });
+        }
+        // planes have an infinite box and are tested on their own
+        let (planes, bounded): (Vec<_>, Vec<_>) = geometries.into_iter().partition(|g| {
+            let aabb = g.get_bounding_box();
+            !(aabb.min.iter().all(|x| x.is_finite()) && aabb.max.iter().all(|x| x.is_finite()))
+        });
+        let len = bounded.len();
+        self.bvh = if len > 0 {
+            Some(Arc::new(BvhNode::new(bounded, 0, len)) as Arc<dyn Geometry + Send + Sync>)
+        } else {
+            None
+        };
+        self.planes = planes;
+        self.light_sampler = LightSampler::new(&self.lights, self.radius());
+        Ok(())
+    }
+
+    pub fn lights(&self) -> &[Arc<dyn Light + Send + Sync>] {
+        &self.lights
+    }
+
+    // replaces the lights along with the sampler that picks among them
+    pub fn set_lights(&mut self, lights: Vec<Arc<dyn Light + Send + Sync>>) {
+        self.lights = lights;
+        self.light_sampler = LightSampler::new(&self.lights, self.radius());
+    }
+
+    pub fn light_sampler(&self) -> &LightSampler {
+        &self.light_sampler
+    }
+
+    // half the diagonal of the bounded geometry
+    fn radius(&self) -> f64 {
+        self.bvh.as_ref().map_or(0.0, |bvh| {
+            let aabb = bvh.get_bounding_box();
+            (aabb.max - aabb.min).norm() / 2.0
+        })
+    }
+
+    // closest hit in the BVH or on any of the planes
+    pub fn intersects(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let hit = self
+            .bvh
+            .as_ref()
+            .and_then(|bvh| bvh.intersects(ray, t_min, t_max));
+        self.planes.iter().fold(hit, |closest, plane| {
+            let t_max = closest.as_ref().map_or(t_max, |r| r.dist);
+            plane.intersects(ray, t_min, t_max).or(closest)
+        })
+    }
+
     pub fn trace(&self, ray: &Ray, depth: i32) -> Color {
         if depth >= 15 {
             return Color::zeros();
         }
-        self.bvh
-            .intersects(ray, 0.0, INFINITY)
-            .map_or(Color::zeros(), |record| {
+        self.intersects(ray, 0.0, f64::INFINITY).map_or_else(
+            || self.background_radiance(ray),
+            |record| {
                 let wo = (-1.0 * ray.dir).normalize();
+                let inside = record.normal.dot(&wo) < 0.0;
                 // revert normal if we hit the inside surface
-                let adjusted_normal = record.normal * record.normal.dot(&wo).signum();
+                let adjusted_normal = if inside {
+                    -record.normal
+                } else {
+                    record.normal
+                };
                 let rayhit = RayHit {
                     ray,
                     hit_point: record.hit_point,
                     material_id: record.material_id,
+                    vertex_color: record.vertex_color,
                     normal: adjusted_normal,
+                    offset: offset_along(&record.geometric_normal, &record.error),
+                    inside,
                     world: self,
                     depth,
                 };
                 self.get_material(record.material_id).shade(&rayhit)
-            })
+            },
+        )
     }
 
-    pub fn is_in_shadow(&self, point: &Point3<f64>, dir: &Vec3, t_max: f64) -> bool {
-        let offset = 0.00001 * dir;
-        let shadow_ray = Ray::new(point + offset, *dir);
-        self.bvh
-            .intersects(&shadow_ray, 0.0, t_max)
+    fn background_radiance(&self, ray: &Ray) -> Color {
+        self.background
+            .as_ref()
+            .map_or(Color::zeros(), |background| background.radiance(&ray.dir))
+    }
+
+    // Shadow rays come from RayHit::spawn_ray, see LightSample::unless_shadowed
+    // for how t_max keeps clear of the light's surface
+    pub fn is_in_shadow(&self, shadow_ray: &Ray, t_max: f64) -> bool {
+        self.intersects(shadow_ray, 0.0, t_max)
             .filter(|record| {
                 !matches!(self.get_material(record.material_id), Material::Emissive(_))
             })
             .is_some()
     }
 
+    // every material id is checked when the world is constructed
     pub fn get_material(&self, material_id: usize) -> &Material {
-        self.materials.get(&material_id).unwrap()
+        &self.materials[&material_id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brdf::Lambertian;
+    use crate::geometric_object::{Csg, Sphere};
+    use crate::light::{Intensity, PointLight};
+    use crate::material::Matte;
+    use nalgebra::Point3;
+    use rand::thread_rng;
+
+    #[test]
+    fn unknown_material_inside_csg_is_rejected() {
+        let sphere = |material_id, x| {
+            Arc::new(Sphere::new(
+                material_id,
+                1.0,
+                Point3::new(x, 0.0, 0.0),
+                10.0,
+            )) as Arc<dyn Geometry + Send + Sync>
+        };
+        let csg = Arc::new(Csg::union(sphere(0, 0.0), sphere(7, 1.0)).unwrap());
+        let matte = Matte::new(
+            Lambertian::new(0.5, Color::zeros()),
+            Lambertian::new(1.0, Color::repeat(1.0)),
+        );
+        let materials = HashMap::from([(0, Box::new(Material::Matte(matte)))]);
+        let vp = ViewPlane {
+            hres: 10,
+            vres: 10,
+            pixel_size: 1.0,
+        };
+        let ambient_light = AmbientLight {
+            ls: 0.0,
+            cl: Color::zeros(),
+        };
+        let world = World::new(vp, vec![csg], vec![], ambient_light, materials);
+        assert!(matches!(
+            world,
+            Err(SceneError::UnknownMaterial { material, .. }) if material == "7"
+        ));
+    }
+
+    #[test]
+    fn set_lights_rebuilds_the_sampler() {
+        let light_world =
+            crate::light::tests::world(vec![Arc::new(Sphere::new(0, 1.0, Point3::origin(), 10.0))]);
+        let mut world = light_world;
+        assert!(world
+            .light_sampler()
+            .sample_power(&mut thread_rng())
+            .is_none());
+        let point = |x| {
+            Arc::new(PointLight::new(
+                Intensity::Candela(1.0),
+                Color::repeat(1.0),
+                Point3::new(x, 0.0, 0.0),
+                None,
+            )) as Arc<dyn Light + Send + Sync>
+        };
+        world.set_lights(vec![point(0.0), point(1.0)]);
+        for _ in 0..100 {
+            let (i, _) = world
+                .light_sampler()
+                .sample_power(&mut thread_rng())
+                .unwrap();
+            assert!(i < world.lights().len());
+        }
     }
 }